@@ -20,7 +20,30 @@ async fn help(ctx: &Context, msg: &Message) -> CommandResult {
                 embed.description("Help (eng only)");
                 embed.field("ping", "pong", true);
                 embed.field("help", "this page", true);
-                embed.field("vote", crate::cmds::vote::help(), false);
+                for (i, field) in crate::cmds::vote::help().into_iter().enumerate() {
+                    let name = if i == 0 { "vote" } else { "vote (continued)" };
+                    embed.field(name, field, false);
+                }
+                embed.field(
+                    "export",
+                    "Upload results of a vote as CSV and JSON: export <message id or link>",
+                    false,
+                );
+                embed.field(
+                    "template",
+                    "Reuse votes in this server: template save <name> [options] title choices..., template use <name> [overrides], template list, template delete <name>",
+                    false,
+                );
+                embed.field(
+                    "schedule",
+                    "Post a vote on a schedule: schedule add <1d|\"0 9 * * Mon Asia/Tokyo\"> [options] title choices..., schedule list, schedule cancel <id>",
+                    false,
+                );
+                embed.field(
+                    "/poll",
+                    "Privately show the results of a vote or your own votes: /poll results|myvotes <message id or link>",
+                    false,
+                );
                 embed.colour(Colour::ORANGE);
                 embed
             });
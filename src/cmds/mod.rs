@@ -1,4 +1,15 @@
 pub mod ping;
 pub mod vote;
 pub mod help;
-mod utils;
\ No newline at end of file
+mod utils;
+
+use serenity::builder::CreateApplicationCommands;
+
+pub fn register(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+    commands
+        .create_application_command(|command| vote::register(command))
+        .create_application_command(|command| vote::export::register(command))
+        .create_application_command(|command| vote::query::register(command))
+        .create_application_command(|command| vote::template::register(command))
+        .create_application_command(|command| vote::recurring::register(command))
+}
@@ -0,0 +1,89 @@
+use once_cell::sync::Lazy;
+use plotters::{
+    prelude::*,
+    style::{register_font, FontStyle},
+};
+
+use super::{choice::Choice, tally::Tally};
+use crate::error::{Error, Result};
+
+pub const FILENAME: &str = "chart.png";
+
+const WIDTH: u32 = 800;
+const ROW: i32 = 36;
+const PADDING: i32 = 16;
+/// Left edge of the bars, choice names are written before it
+const BARS: i32 = 260;
+const BAR_WIDTH: i32 = 400;
+
+const BAR: RGBColor = RGBColor(0x58, 0x65, 0xf2);
+const WINNER: RGBColor = RGBColor(0xe6, 0x7e, 0x22);
+
+static FONT: Lazy<bool> =
+    Lazy::new(|| register_font("sans-serif", FontStyle::Normal, notosans::REGULAR_TTF).is_ok());
+
+fn shorten(s: &str, len: usize) -> String {
+    if s.chars().count() > len {
+        format!("{}…", s.chars().take(len - 1).collect::<String>())
+    } else {
+        s.to_string()
+    }
+}
+
+/// Draws a horizontal bar per choice as PNG, highlighting the winners once ended.
+pub fn bar_chart(title: &str, choices: &[Choice], tally: &Tally, ended: bool) -> Result<Vec<u8>> {
+    if !*FONT {
+        return Err(Error::Chart("Failed to load font".to_string()));
+    }
+    let height = (PADDING * 3 + ROW * (choices.len() as i32 + 1)) as u32;
+    let mut buffer = vec![0; (WIDTH * height * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (WIDTH, height)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let font = ("sans-serif", 18).into_font();
+        root.draw(&Text::new(
+            shorten(title, 60),
+            (PADDING, PADDING),
+            ("sans-serif", 24).into_font().color(&BLACK),
+        ))?;
+        let max = tally.counts.iter().copied().max().unwrap_or(0);
+        for (i, choice) in choices.iter().enumerate() {
+            let y = PADDING * 2 + ROW * (i as i32 + 1);
+            let count = tally.counts[i];
+            let width = (BAR_WIDTH * count as i32)
+                .checked_div(max as i32)
+                .unwrap_or(0);
+            let colour = if ended && tally.winners.contains(&i) {
+                WINNER
+            } else {
+                BAR
+            };
+            root.draw(&Text::new(
+                shorten(&choice.name, 24),
+                (PADDING, y + 6),
+                font.color(&BLACK),
+            ))?;
+            root.draw(&Rectangle::new(
+                [(BARS, y + 4), (BARS + width.max(2), y + ROW - 4)],
+                colour.filled(),
+            ))?;
+            let value = match (count * 100).checked_div(tally.total) {
+                Some(ratio) => format!("{} ({}%)", count, ratio),
+                None => count.to_string(),
+            };
+            root.draw(&Text::new(
+                value,
+                (BARS + width.max(2) + 8, y + 6),
+                font.color(&BLACK),
+            ))?;
+        }
+        root.present()?;
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, WIDTH, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&buffer)?;
+    Ok(png)
+}
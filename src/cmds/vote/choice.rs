@@ -0,0 +1,128 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::{application::component::ButtonStyle, channel::ReactionType};
+
+/// A choice written as `[emoji] name[|style]`, e.g. `"👍 Yes|success"`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(from = "Stored")]
+pub struct Choice {
+    pub name: String,
+    /// Unicode emoji, or custom emoji as `<:name:id>`
+    pub emoji: Option<String>,
+    pub style: Style,
+}
+
+/// Polls stored before choices had emoji or styles hold plain names.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Name(String),
+    Choice {
+        name: String,
+        #[serde(default)]
+        emoji: Option<String>,
+        #[serde(default)]
+        style: Style,
+    },
+}
+
+impl From<Stored> for Choice {
+    fn from(stored: Stored) -> Self {
+        match stored {
+            Stored::Name(name) => Choice {
+                name,
+                emoji: None,
+                style: Style::default(),
+            },
+            Stored::Choice { name, emoji, style } => Choice { name, emoji, style },
+        }
+    }
+}
+
+impl From<&str> for Choice {
+    /// Unknown styles and emoji without a name are kept as part of the name.
+    fn from(s: &str) -> Self {
+        let (mut name, style) = match s.rsplit_once('|') {
+            Some((name, style)) => match style.parse() {
+                Ok(style) => (name.trim(), style),
+                Err(_) => (s, Style::default()),
+            },
+            None => (s, Style::default()),
+        };
+        let mut emoji = None;
+        if let Some((first, rest)) = name.split_once(char::is_whitespace) {
+            if is_emoji(first) && !rest.trim().is_empty() {
+                emoji = Some(first.to_string());
+                name = rest.trim();
+            }
+        }
+        Choice {
+            name: name.to_string(),
+            emoji,
+            style,
+        }
+    }
+}
+
+impl Choice {
+    pub fn reaction(&self) -> Option<ReactionType> {
+        ReactionType::try_from(self.emoji.as_deref()?).ok()
+    }
+}
+
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.emoji {
+            Some(emoji) => write!(f, "{} {}", emoji, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Custom emoji markup, or a Unicode emoji from the emoji table. Other symbols
+/// such as `€` or `½` would be rejected by Discord, so they stay in the name.
+fn is_emoji(word: &str) -> bool {
+    if word.starts_with('<') {
+        return matches!(
+            ReactionType::try_from(word),
+            Ok(ReactionType::Custom { .. })
+        );
+    }
+    emojis::get(word).is_some()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    #[default]
+    Primary,
+    Secondary,
+    Success,
+    Danger,
+}
+
+impl Style {
+    pub fn button_style(&self) -> ButtonStyle {
+        match self {
+            Style::Primary => ButtonStyle::Primary,
+            Style::Secondary => ButtonStyle::Secondary,
+            Style::Success => ButtonStyle::Success,
+            Style::Danger => ButtonStyle::Danger,
+        }
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "primary" => Ok(Style::Primary),
+            "secondary" => Ok(Style::Secondary),
+            "success" => Ok(Style::Success),
+            "danger" => Ok(Style::Danger),
+            _ => Err(format!("Unknown style {}", s)),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+use crate::error::{Error, Result};
+
+const DATE_TIMES: [&str; 3] = ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"];
+const TIMES: [&str; 3] = ["%H:%M", "%H:%M:%S", "%I:%M%p"];
+
+/// Reads a due date written as RFC3339, as a duration from now like `2h` or `1d12h`,
+/// as a local date like `2024-05-01 18:00`, or in words like `tomorrow 18:00`,
+/// `fri 9:30am` or `18:00`. Local times are read in `tz`.
+pub fn parse(s: &str, now: DateTime<Utc>, tz: Tz) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(due) = DateTime::parse_from_rfc3339(s) {
+        return Ok(due.with_timezone(&Utc));
+    }
+    if let Some(duration) = duration(s) {
+        return now
+            .checked_add_signed(duration)
+            .ok_or_else(|| Error::user("Due date is too far away"));
+    }
+    let local = absolute(s)
+        .or_else(|| relative(&s.to_lowercase(), now.with_timezone(&tz).naive_local()))
+        .ok_or_else(|| {
+            Error::User(format!(
+                "Can't read due date {}, try 2h, 3d, \"tomorrow 18:00\" or RFC3339",
+                s
+            ))
+        })?;
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|due| due.with_timezone(&Utc))
+        .ok_or_else(|| Error::User(format!("{} doesn't exist in {}", local, tz)))
+}
+
+/// Numbers followed by m, h, d or w, which may be combined as in `1d12h`.
+pub fn duration(s: &str) -> Option<Duration> {
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let count = number.parse::<u32>().ok()? as i64;
+        number.clear();
+        let part = match c {
+            'm' => Duration::minutes(count),
+            'h' => Duration::hours(count),
+            'd' => Duration::days(count),
+            'w' => Duration::weeks(count),
+            _ => return None,
+        };
+        total = total.checked_add(&part)?;
+    }
+    if !number.is_empty() || total.is_zero() {
+        return None;
+    }
+    Some(total)
+}
+
+/// A date alone means midnight at its start.
+fn absolute(s: &str) -> Option<NaiveDateTime> {
+    DATE_TIMES
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// `[today|tomorrow|weekday] [time]`, or a time alone meaning its next occurrence.
+/// Days without a time keep the current time of day.
+fn relative(s: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    if let [word] = words[..] {
+        if let Some(time) = time(word) {
+            let today = now.date().and_time(time);
+            return Some(if today > now {
+                today
+            } else {
+                today + Duration::days(1)
+            });
+        }
+    }
+    let (day, time) = match words[..] {
+        [day] => (day, None),
+        [day, t] => (day, Some(time(t)?)),
+        _ => return None,
+    };
+    let days = match day {
+        "today" => 0,
+        "tomorrow" => 1,
+        day => {
+            let weekday: Weekday = day.parse().ok()?;
+            let days =
+                (7 + weekday.num_days_from_monday() - now.weekday().num_days_from_monday()) % 7;
+            // Today only while the time is still ahead
+            if days == 0 && time.is_none_or(|time| time <= now.time()) {
+                7
+            } else {
+                days
+            }
+        }
+    };
+    Some((now.date() + Duration::days(days as i64)).and_time(time.unwrap_or(now.time())))
+}
+
+fn time(s: &str) -> Option<NaiveTime> {
+    if let Some(time) = TIMES
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(s, format).ok())
+    {
+        return Some(time);
+    }
+    // Whole hours like 6pm
+    let hour = s.strip_suffix("am").or_else(|| s.strip_suffix("pm"))?;
+    NaiveTime::parse_from_str(&format!("{}:00{}", hour, &s[hour.len()..]), "%I:%M%p").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wednesday noon
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 5, 12, 0, 0).unwrap()
+    }
+
+    fn at(d: u32, h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, d, h, m, 0).unwrap()
+    }
+
+    fn due(s: &str) -> DateTime<Utc> {
+        parse(s, now(), Tz::UTC).unwrap()
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(due("wed 18:00"), at(5, 18, 0));
+        assert_eq!(due("wed 9:00"), at(12, 9, 0));
+        assert_eq!(due("wed"), at(12, 12, 0));
+        assert_eq!(due("fri 9:30am"), at(7, 9, 30));
+    }
+
+    #[test]
+    fn bare_times() {
+        assert_eq!(due("18:00"), at(5, 18, 0));
+        assert_eq!(due("9:00"), at(6, 9, 0));
+        assert_eq!(due("6pm"), at(5, 18, 0));
+        assert_eq!(due("6am"), at(6, 6, 0));
+        assert_eq!(due("tomorrow 6pm"), at(6, 18, 0));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(due("1d12h"), now() + Duration::hours(36));
+        assert_eq!(due("90m"), at(5, 13, 30));
+        assert!(duration("0m").is_none());
+        assert!(parse("0m", now(), Tz::UTC).is_err());
+        assert!(duration("1d12").is_none());
+    }
+
+    #[test]
+    fn local_dates() {
+        assert_eq!(
+            parse("2024-06-10 18:00", now(), chrono_tz::Asia::Tokyo).unwrap(),
+            at(10, 9, 0)
+        );
+        assert_eq!(due("2024-06-10T18:00:00+02:00"), at(10, 16, 0));
+    }
+
+    #[test]
+    fn dst_transitions() {
+        let berlin = chrono_tz::Europe::Berlin;
+        // Skipped when clocks go forward
+        assert!(parse("2024-03-31 02:30", now(), berlin).is_err());
+        // The earlier of the repeated hour when they go back
+        assert_eq!(
+            parse("2024-10-27 02:30", now(), berlin).unwrap(),
+            Utc.with_ymd_and_hms(2024, 10, 27, 0, 30, 0).unwrap()
+        );
+    }
+}
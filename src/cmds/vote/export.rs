@@ -0,0 +1,200 @@
+use serde_json::json;
+use serenity::{
+    builder::CreateApplicationCommand,
+    framework::standard::{macros::command, CommandResult},
+    model::{
+        application::{
+            command::CommandOptionType,
+            interaction::{
+                application_command::CommandDataOptionValue, Interaction, InteractionResponseType,
+            },
+        },
+        channel::{AttachmentType, Message},
+        id::RoleId,
+        Permissions,
+    },
+    prelude::*,
+};
+use tracing::warn;
+
+use super::{can_manage, error_embed, lookup, member_permissions};
+use crate::error::{Error, Result};
+
+#[command]
+async fn export(ctx: &Context, msg: &Message) -> CommandResult {
+    let roles = msg.member.as_ref().map_or(Vec::new(), |m| m.roles.clone());
+    let result = match msg.content.split_whitespace().nth(1) {
+        Some(poll) => files(
+            poll,
+            msg.guild_id.map_or(0, |guild| *guild.as_u64()),
+            *msg.channel_id.as_u64(),
+            *msg.author.id.as_u64(),
+            &roles,
+            member_permissions(ctx, msg).await,
+        ),
+        None => Err(Error::user("Not enough params")),
+    };
+    match result {
+        Ok(files) => {
+            msg.channel_id
+                .send_files(&ctx.http, files, |msg_res| {
+                    msg_res.reference_message(msg);
+                    msg_res
+                })
+                .await?;
+        }
+        Err(why) => {
+            msg.channel_id
+                .send_message(&ctx.http, |msg_res| {
+                    msg_res.embed(|embed| error_embed(embed, &why));
+                    msg_res.reference_message(msg);
+                    msg_res
+                })
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("export")
+        .description("Export vote results as CSV and JSON")
+        .dm_permission(false);
+    command.create_option(|option| {
+        option
+            .name("poll")
+            .description("Message id or link of the vote")
+            .kind(CommandOptionType::String)
+            .required(true)
+    });
+    command
+}
+
+pub async fn application_command(ctx: &Context, i: &Interaction) {
+    if let Interaction::ApplicationCommand(interaction) = i {
+        let poll = interaction.data.options.iter().find_map(|option| {
+            match (option.name.as_str(), &option.resolved) {
+                ("poll", Some(CommandDataOptionValue::String(poll))) => Some(poll),
+                _ => None,
+            }
+        });
+        let (roles, permissions) = match &interaction.member {
+            Some(member) => (member.roles.clone(), member.permissions),
+            None => (Vec::new(), None),
+        };
+        let result = match poll {
+            Some(poll) => files(
+                poll,
+                interaction.guild_id.map_or(0, |guild| *guild.as_u64()),
+                *interaction.channel_id.as_u64(),
+                *interaction.user.id.as_u64(),
+                &roles,
+                permissions,
+            ),
+            None => Err(Error::user("Not enough params")),
+        };
+        if let Err(why) = interaction
+            .create_interaction_response(&ctx.http, |res| {
+                res.kind(InteractionResponseType::ChannelMessageWithSource);
+                res.interaction_response_data(|data| {
+                    match result {
+                        Ok(files) => {
+                            data.add_files(files);
+                        }
+                        Err(why) => {
+                            data.embed(|embed| error_embed(embed, &why));
+                        }
+                    }
+                    data.ephemeral(true);
+                    data
+                });
+                res
+            })
+            .await
+        {
+            warn!("Failed to respond to /export: {}", why);
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Builds the CSV and JSON exports, omitting voter ids of anonymous polls.
+/// Results are only exported for ended polls, unless requested by someone who
+/// may manage the poll.
+fn files(
+    poll: &str,
+    guild: u64,
+    channel: u64,
+    user: u64,
+    roles: &[RoleId],
+    permissions: Option<Permissions>,
+) -> Result<Vec<AttachmentType<'static>>> {
+    let (id, poll, votes) = lookup(poll, guild, channel)?;
+    if !votes.isended && !can_manage(&poll, user, roles, permissions) {
+        return Err(Error::user(
+            "Only the owner or moderators can export a running vote",
+        ));
+    }
+    let args = &poll.args;
+
+    let mut csv = if args.anonymous {
+        "choice,name,time\n".to_string()
+    } else {
+        "choice,name,voter,time\n".to_string()
+    };
+    let mut choices = Vec::new();
+    for (i, choice) in votes.votes.iter().enumerate() {
+        let mut rows = Vec::new();
+        for detail in choice {
+            let time = detail.time.to_rfc3339();
+            if args.anonymous {
+                csv += &format!("{},{},{}\n", i, csv_field(&args.choices[i].name), time);
+                rows.push(json!({ "time": time }));
+            } else {
+                csv += &format!(
+                    "{},{},{},{}\n",
+                    i,
+                    csv_field(&args.choices[i].name),
+                    detail.id,
+                    time
+                );
+                rows.push(json!({ "voter": detail.id.to_string(), "time": time }));
+            }
+        }
+        choices.push(json!({
+            "name": args.choices[i].name,
+            "emoji": args.choices[i].emoji,
+            "votes": rows,
+        }));
+    }
+    let json = json!({
+        "id": id.to_string(),
+        "title": args.title,
+        "description": args.description,
+        "method": args.method,
+        "anonymous": args.anonymous,
+        "ended": votes.isended,
+        "created": poll.created.to_rfc3339(),
+        "due": args.due.to_rfc3339(),
+        "choices": choices,
+    });
+
+    Ok(vec![
+        AttachmentType::Bytes {
+            data: csv.into_bytes().into(),
+            filename: format!("vote_{}.csv", id),
+        },
+        AttachmentType::Bytes {
+            data: serde_json::to_vec_pretty(&json)?.into(),
+            filename: format!("vote_{}.json", id),
+        },
+    ])
+}
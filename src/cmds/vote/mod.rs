@@ -0,0 +1,1816 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use getopts::{Matches, Options};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    builder::{CreateApplicationCommand, CreateComponents, CreateEmbed},
+    framework::standard::{macros::command, CommandResult},
+    http::Http,
+    model::{
+        application::{
+            command::CommandOptionType,
+            component::ButtonStyle,
+            interaction::{
+                application_command::{
+                    ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+                },
+                message_component::MessageComponentInteraction,
+                Interaction, InteractionResponseType,
+            },
+        },
+        channel::{AttachmentType, Message},
+        id::{AttachmentId, RoleId},
+        user::User,
+        Permissions,
+    },
+    prelude::*,
+    utils::Colour,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    cmds::utils,
+    config::{self, Archive, CONFIG},
+    crypto::{Keys, KEYS},
+    error::{Error, Result},
+    store::{self, PollStore, Table, STORE},
+};
+
+use self::{
+    choice::Choice,
+    tally::{Ballot, Method, Tally},
+};
+
+mod chart;
+mod choice;
+mod due;
+pub mod export;
+pub mod query;
+pub mod recurring;
+mod tally;
+pub mod template;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Args {
+    title: String,
+    description: String,
+    choices: Vec<Choice>,
+    due: DateTime<Utc>,
+    anonymous: bool,
+    mask: bool,
+    max: u8,
+    editable: bool,
+    duplicate: bool,
+    #[serde(default)]
+    method: Method,
+    /// Vote with a select menu instead of buttons
+    #[serde(default)]
+    menu: bool,
+    /// Show a bar chart while the vote is running, not only once ended
+    #[serde(default)]
+    chart: bool,
+    /// Show a text progress bar in every result field
+    #[serde(default)]
+    bars: bool,
+    /// Order result fields by count, leading choice first
+    #[serde(default)]
+    sort: bool,
+    #[serde(default)]
+    allow_roles: Vec<u64>,
+    #[serde(default)]
+    deny_roles: Vec<u64>,
+    /// Voters needed for the outcome to count, 0 for none
+    #[serde(default)]
+    quorum: usize,
+    /// Share of the votes the winner needs, as numerator and denominator
+    #[serde(default)]
+    threshold: Option<(u32, u32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Poll {
+    args: Args,
+    creator: u64,
+    channel: u64,
+    guild: u64,
+    created: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Votes {
+    votes: Vec<Vec<VoteDetail>>,
+    lastupdate: DateTime<Utc>,
+    isended: bool,
+}
+
+impl Votes {
+    fn new(choices: usize) -> Self {
+        Votes {
+            votes: vec![Vec::new(); choices],
+            lastupdate: Utc::now(),
+            isended: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct VoteDetail {
+    id: u64,
+    time: DateTime<Utc>,
+}
+
+/// Four rows of five buttons, the last row holds End/Restart
+const MAX_BUTTONS: usize = 20;
+/// Options a select menu can hold
+const MAX_OPTIONS: usize = 25;
+
+pub static OPTIONS: Lazy<Options> = Lazy::new(init);
+
+#[command]
+async fn vote(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild_id.map_or(0, |guild| *guild.as_u64());
+    post(ctx, msg, parser(&msg.content, config::timezone(guild))).await
+}
+
+/// Replies with the new poll, or with the error which prevented it.
+async fn post(ctx: &Context, msg: &Message, parsed: Result<Args>) -> CommandResult {
+    if let Err(why) = parsed {
+        msg.channel_id
+            .send_message(&ctx.http, |msg_res| {
+                msg_res.embed(|embed| error_embed(embed, &why));
+                msg_res.reference_message(msg);
+                msg_res
+            })
+            .await?;
+    } else {
+        let args = parsed.unwrap();
+        let sent = msg
+            .channel_id
+            .send_message(&ctx.http, |msg_res| {
+                msg_res.embed(|embed| create_embed(embed, &msg.author, &args));
+                msg_res.reference_message(msg);
+                msg_res.components(|f| create_components(f, &args, false));
+                msg_res
+            })
+            .await?;
+        let guild = msg.guild_id.map_or(0, |guild| *guild.as_u64());
+        open(&ctx.http, &sent, args, &msg.author, guild)?;
+    }
+
+    Ok(())
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command.name("vote").description("Create a new vote");
+    command.create_option(|option| {
+        option
+            .name("title")
+            .description("Title of the vote")
+            .kind(CommandOptionType::String)
+            .required(true)
+    });
+    command.create_option(|option| {
+        option
+            .name("choices")
+            .description("Space separated choices, quote choices containing spaces, written as [emoji] name[|style]")
+            .kind(CommandOptionType::String)
+            .required(true)
+    });
+    command.create_option(|option| {
+        option
+            .name("description")
+            .description("Set description")
+            .kind(CommandOptionType::String)
+    });
+    command.create_option(|option| {
+        option
+            .name("due")
+            .description("Set due time/date, e.g. 2h, 3d, \"tomorrow 18:00\" or RFC3339")
+            .kind(CommandOptionType::String)
+    });
+    command.create_option(|option| {
+        option
+            .name("max")
+            .description("Max vote")
+            .kind(CommandOptionType::Integer)
+            .min_int_value(1)
+            .max_int_value(u8::MAX)
+    });
+    command.create_option(|option| {
+        option
+            .name("quorum")
+            .description("Voters needed for the vote to pass")
+            .kind(CommandOptionType::Integer)
+            .min_int_value(1)
+    });
+    command.create_option(|option| {
+        option
+            .name("threshold")
+            .description("Share of the votes the winner needs to pass, e.g. 2/3 or 60%")
+            .kind(CommandOptionType::String)
+    });
+    command.create_option(|option| {
+        option
+            .name("method")
+            .description("Tally method")
+            .kind(CommandOptionType::String);
+        for name in Method::NAMES.split(", ") {
+            option.add_string_choice(name, name);
+        }
+        option
+    });
+    for (name, description) in [
+        ("allow-role", "Only members with this role may vote"),
+        ("deny-role", "Members with this role may not vote"),
+    ] {
+        command.create_option(|option| {
+            option
+                .name(name)
+                .description(description)
+                .kind(CommandOptionType::Role)
+        });
+    }
+    for (name, description) in [
+        ("anonymous", "Anonymous vote"),
+        ("mask", "Mask vote status"),
+        ("noedit", "Disable editing vote"),
+        ("duplicate", "Allow duplicate vote"),
+        ("select", "Vote with a select menu"),
+        ("chart", "Show a result chart while running"),
+        ("bars", "Show progress bars in the results"),
+        ("sort", "Sort results, leading choice first"),
+    ] {
+        command.create_option(|option| {
+            option
+                .name(name)
+                .description(description)
+                .kind(CommandOptionType::Boolean)
+        });
+    }
+    command
+}
+
+pub async fn application_command(ctx: &Context, i: &Interaction) {
+    if let Interaction::ApplicationCommand(interaction) = i {
+        let line = command_line(&interaction.data.options);
+        let guild = interaction.guild_id.map_or(0, |guild| *guild.as_u64());
+        respond(ctx, interaction, parser(&line, config::timezone(guild))).await;
+    }
+}
+
+/// Answers a slash command with the new poll, or privately with the error which prevented it.
+async fn respond(ctx: &Context, interaction: &ApplicationCommandInteraction, parsed: Result<Args>) {
+    let result = interaction
+        .create_interaction_response(&ctx.http, |res| {
+            res.kind(InteractionResponseType::ChannelMessageWithSource);
+            res.interaction_response_data(|data| {
+                match &parsed {
+                    Ok(args) => {
+                        data.embed(|embed| create_embed(embed, &interaction.user, args));
+                        data.components(|f| create_components(f, args, false));
+                    }
+                    Err(why) => {
+                        data.embed(|embed| error_embed(embed, why));
+                        data.ephemeral(true);
+                    }
+                }
+                data
+            });
+            res
+        })
+        .await;
+    if let Err(why) = result {
+        warn!("Failed to respond to /vote: {}", why);
+        return;
+    }
+    if let Ok(args) = parsed {
+        let guild = interaction.guild_id.map_or(0, |guild| *guild.as_u64());
+        match interaction.get_interaction_response(&ctx.http).await {
+            Ok(sent) => {
+                if let Err(why) = open(&ctx.http, &sent, args, &interaction.user, guild) {
+                    error!("Failed to open poll {}: {}", sent.id, why);
+                }
+            }
+            Err(why) => warn!("Failed to fetch /vote response: {}", why),
+        }
+    }
+}
+
+fn string_option(options: &[CommandDataOption], name: &str) -> Option<String> {
+    options
+        .iter()
+        .find_map(|option| match (option.name.as_str(), &option.resolved) {
+            (n, Some(CommandDataOptionValue::String(value))) if n == name => Some(value.clone()),
+            _ => None,
+        })
+}
+
+/// Rebuilds the equivalent prefix command, so slash polls go through the same parser.
+fn command_line(options: &[CommandDataOption]) -> String {
+    let mut free = Vec::new();
+    let mut opts = Vec::new();
+    for option in options {
+        match (option.name.as_str(), &option.resolved) {
+            ("title", Some(CommandDataOptionValue::String(title))) => free.insert(0, quote(title)),
+            ("choices", Some(CommandDataOptionValue::String(choices))) => {
+                free.extend(split_args(choices).iter().map(|choice| quote(choice)))
+            }
+            (name, Some(CommandDataOptionValue::String(value))) => {
+                opts.push(format!("--{} {}", name, quote(value)))
+            }
+            (name, Some(CommandDataOptionValue::Integer(value))) => {
+                opts.push(format!("--{} {}", name, value))
+            }
+            (name, Some(CommandDataOptionValue::Boolean(true))) => opts.push(format!("--{}", name)),
+            (name, Some(CommandDataOptionValue::Role(role))) => {
+                opts.push(format!("--{} {}", name, role.id))
+            }
+            _ => {}
+        }
+    }
+    let mut line = format!("{}vote", CONFIG.infos.prefix);
+    for opt in opts {
+        line = format!("{} {}", line, opt);
+    }
+    format!("{} -- {}", line, free.join(" "))
+}
+
+fn create_embed<'a>(embed: &'a mut CreateEmbed, author: &User, args: &Args) -> &'a mut CreateEmbed {
+    embed.author(|a| {
+        a.icon_url(author.face());
+        a.name(&author.name);
+        a
+    });
+    embed.title(&args.title);
+    embed.description(description(args));
+    for choice in &args.choices {
+        embed.field(choice.to_string(), "-", true);
+    }
+    embed.colour(Colour::ORANGE);
+    embed
+}
+
+fn description(args: &Args) -> String {
+    format!("{}\n\n{}", args.description, settings(args))
+}
+
+/// Timestamp markup shows the due time in the locale of each viewer.
+fn settings(args: &Args) -> String {
+    let mut settings = vec![format!(
+        "Due <t:{ts}:f> (<t:{ts}:R>)",
+        ts = args.due.timestamp()
+    )];
+    if args.method != Method::Plurality {
+        settings.push(args.method.tally_method().name().to_string());
+    }
+    settings.push(match args.method {
+        Method::Score => format!("max score {}", args.max),
+        _ if args.method.ranked() => format!("rank up to {}", args.max),
+        _ => format!("max {} vote(s)", args.max),
+    });
+    for (flag, name) in [
+        (args.anonymous, "anonymous"),
+        (args.mask, "masked"),
+        (args.editable, "editable"),
+        (args.duplicate, "duplicate votes"),
+    ] {
+        if flag {
+            settings.push(name.to_string());
+        }
+    }
+    if args.quorum > 0 {
+        settings.push(format!("quorum {}", args.quorum));
+    }
+    if let Some(threshold) = args.threshold {
+        settings.push(format!("threshold {}", ratio(threshold)));
+    }
+    format!("*{}*", settings.join(" · "))
+}
+
+fn create_components<'a>(
+    f: &'a mut CreateComponents,
+    args: &Args,
+    disabled: bool,
+) -> &'a mut CreateComponents {
+    if args.menu {
+        let max = if multi_select(args) {
+            (args.max as usize).clamp(1, args.choices.len())
+        } else {
+            1
+        };
+        f.create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id("choices");
+                if max > 1 {
+                    menu.placeholder(format!("Choose up to {} choice(s)", max));
+                    menu.min_values(0);
+                } else {
+                    menu.placeholder("Choose a choice");
+                }
+                menu.max_values(max as u64);
+                menu.disabled(disabled);
+                menu.options(|options| {
+                    for (i, choice) in args.choices.iter().enumerate() {
+                        options.create_option(|option| {
+                            option.label(choice.name.chars().take(100).collect::<String>());
+                            option.value(i);
+                            if let Some(emoji) = choice.reaction() {
+                                option.emoji(emoji);
+                            }
+                            option
+                        });
+                    }
+                    options
+                });
+                menu
+            });
+            row
+        });
+    } else {
+        let mut count = 0;
+        for chunk in args.choices.chunks(5) {
+            f.create_action_row(|row| {
+                for choice in chunk {
+                    row.create_button(|button| {
+                        button.label(&choice.name);
+                        if let Some(emoji) = choice.reaction() {
+                            button.emoji(emoji);
+                        }
+                        button.style(choice.style.button_style());
+                        button.custom_id(format!("choice_{}", count));
+                        button.disabled(disabled);
+                        button
+                    });
+                    count += 1;
+                }
+                row
+            });
+        }
+    }
+    f.create_action_row(|row| {
+        row.create_button(|button| {
+            button.label("End/Restart");
+            button.style(ButtonStyle::Danger);
+            button.custom_id("toggle");
+            button
+        });
+        row
+    });
+    f
+}
+
+/// Starts tracking a freshly posted poll.
+fn open(http: &Arc<Http>, sent: &Message, args: Args, creator: &User, guild: u64) -> Result<()> {
+    let poll = Poll {
+        args,
+        creator: *creator.id.as_u64(),
+        channel: *sent.channel_id.as_u64(),
+        guild,
+        created: Utc::now(),
+    };
+    let votes = Votes::new(poll.args.choices.len());
+    save_poll(sent.id.as_u64(), &poll)?;
+    save(sent.id.as_u64(), &votes)?;
+    schedule(http.clone(), *sent.id.as_u64(), poll.args.due);
+    Ok(())
+}
+
+/// Polls created before the definition was stored only exist in the embed footer,
+/// so parse it once and persist the result.
+fn migrate(org_msg: &Message, guild: u64) -> Result<Poll> {
+    let embed = org_msg
+        .embeds
+        .first()
+        .ok_or_else(|| Error::Corrupted("Poll embed not found".to_string()))?;
+    let footer = embed
+        .footer
+        .as_ref()
+        .ok_or_else(|| Error::Corrupted("Poll footer not found".to_string()))?;
+    // Legacy footers list the options unquoted
+    let tokens: Vec<String> = footer.text.split_whitespace().map(str::to_string).collect();
+    let args = build(tokens.get(1..).unwrap_or_default(), None, Tz::UTC)?;
+    let creator = embed
+        .author
+        .as_ref()
+        .and_then(|author| author.icon_url.as_ref())
+        .map_or(0, |url| utils::icon_url_to_uid(url));
+    let poll = Poll {
+        args,
+        creator,
+        channel: *org_msg.channel_id.as_u64(),
+        guild,
+        created: org_msg.timestamp.with_timezone(&Utc),
+    };
+    save_poll(org_msg.id.as_u64(), &poll)?;
+    Ok(poll)
+}
+
+pub async fn interaction_create(ctx: &Context, i: &Interaction) {
+    if let Interaction::MessageComponent(interaction) = i {
+        if let Err(why) = component(ctx, interaction).await {
+            followup(&ctx.http, interaction, "error", why.report(), Colour::RED).await;
+        }
+    }
+}
+
+async fn component(ctx: &Context, interaction: &MessageComponentInteraction) -> Result<()> {
+    interaction
+        .create_interaction_response(&ctx.http, |res| {
+            res.kind(InteractionResponseType::DeferredUpdateMessage);
+            res
+        })
+        .await?;
+    let org_msg = &interaction.message;
+    let poll = match load_poll(org_msg.id.as_u64())? {
+        Some(poll) => poll,
+        None => {
+            let guild = interaction.guild_id.map_or(0, |guild| *guild.as_u64());
+            let poll = migrate(org_msg, guild)?;
+            schedule(ctx.http.clone(), *org_msg.id.as_u64(), poll.args.due);
+            poll
+        }
+    };
+    let user = *interaction.user.id.as_u64();
+    let (roles, permissions) = match &interaction.member {
+        Some(member) => (member.roles.clone(), member.permissions),
+        None => (Vec::new(), None),
+    };
+    let custom_id = interaction.data.custom_id.as_str();
+    if custom_id == "choices" || custom_id.starts_with("choice_") {
+        let nums = if custom_id == "choices" {
+            interaction
+                .data
+                .values
+                .iter()
+                .map(|value| choice_num(&poll.args, value))
+                .collect::<Result<Vec<u8>>>()?
+        } else {
+            vec![choice_num(&poll.args, &custom_id["choice_".len()..])?]
+        };
+        let select = custom_id == "choices" && multi_select(&poll.args);
+        if !select && nums.len() != 1 {
+            return Err(Error::user("Choose one choice at a time"));
+        }
+        // Applied within the store update, so simultaneous clicks can't
+        // overwrite each other
+        let result = update(
+            STORE.as_ref(),
+            &KEYS,
+            org_msg.id.as_u64(),
+            poll.args.choices.len(),
+            |votes| {
+                if select {
+                    selector(&poll.args, votes, &nums, &user, &roles)
+                } else {
+                    validator(&poll.args, votes, &nums[0], &user, &roles)
+                }
+            },
+        );
+        let votes = match result {
+            Ok(votes) => votes,
+            Err(Error::User(why)) => {
+                followup(
+                    &ctx.http,
+                    interaction,
+                    "error",
+                    format!("Vote wasn't counted: {}", why),
+                    Colour::RED,
+                )
+                .await;
+                return Ok(());
+            }
+            Err(why) => return Err(why),
+        };
+        followup(
+            &ctx.http,
+            interaction,
+            "Vote recorded",
+            current_votes(&poll.args, &votes, user),
+            Colour::ORANGE,
+        )
+        .await;
+    } else if custom_id == "toggle" {
+        if !can_manage(&poll, user, &roles, permissions) {
+            return Err(Error::user(
+                "Only the owner or moderators can end or restart this vote",
+            ));
+        }
+        update(
+            STORE.as_ref(),
+            &KEYS,
+            org_msg.id.as_u64(),
+            poll.args.choices.len(),
+            |mut votes| {
+                votes.isended = !votes.isended;
+                Ok(votes)
+            },
+        )?;
+    }
+
+    rerender(ctx.http.clone(), org_msg.clone()).await;
+    Ok(())
+}
+
+fn choice_num(args: &Args, value: &str) -> Result<u8> {
+    value
+        .parse()
+        .ok()
+        .filter(|num| (*num as usize) < args.choices.len())
+        .ok_or_else(|| Error::user("Unknown choice"))
+}
+
+/// Whether the select menu submits a whole selection at once. Ranked and
+/// duplicate votes depend on every single pick, so their menu acts like buttons.
+fn multi_select(args: &Args) -> bool {
+    args.menu && !args.method.ranked() && !args.duplicate
+}
+
+fn error_embed<'a>(embed: &'a mut CreateEmbed, why: &Error) -> &'a mut CreateEmbed {
+    embed.title("Error");
+    embed.description(why.report());
+    embed.footer(|f| {
+        f.text(Utc::now().to_rfc2822());
+        f
+    });
+    embed.colour(Colour::RED);
+    embed
+}
+
+/// Shows what a command did under `title`, or the error which stopped it.
+fn result_embed<'a>(
+    embed: &'a mut CreateEmbed,
+    title: &str,
+    result: Result<String>,
+) -> &'a mut CreateEmbed {
+    match result {
+        Ok(description) => {
+            embed.title(title);
+            embed.description(description);
+            embed.footer(|f| {
+                f.text(Utc::now().to_rfc2822());
+                f
+            });
+            embed.colour(Colour::ORANGE);
+            embed
+        }
+        Err(why) => error_embed(embed, &why),
+    }
+}
+
+/// Answers a slash command with a result only its user can see.
+async fn reply(
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    title: &str,
+    result: Result<String>,
+) {
+    if let Err(why) = interaction
+        .create_interaction_response(&ctx.http, |res| {
+            res.kind(InteractionResponseType::ChannelMessageWithSource);
+            res.interaction_response_data(|data| {
+                data.embed(|embed| result_embed(embed, title, result));
+                data.ephemeral(true);
+                data
+            });
+            res
+        })
+        .await
+    {
+        warn!("Failed to respond to /{}: {}", interaction.data.name, why);
+    }
+}
+
+/// Sends feedback only the clicking user can see.
+async fn followup(
+    http: &Http,
+    interaction: &MessageComponentInteraction,
+    title: &str,
+    description: String,
+    colour: Colour,
+) {
+    if let Err(why) = interaction
+        .create_followup_message(http, |msg| {
+            msg.embed(|embed| {
+                embed.title(title);
+                embed.description(description);
+                embed.footer(|footer| {
+                    footer.text(Utc::now().to_rfc2822());
+                    footer
+                });
+                embed.colour(colour);
+                embed
+            });
+            msg.ephemeral(true);
+            msg
+        })
+        .await
+    {
+        warn!("Failed to send followup: {}", why);
+    }
+}
+
+fn current_votes(args: &Args, votes: &Votes, user: u64) -> String {
+    let ballot = ballots(votes).remove(&user).unwrap_or_default();
+    if ballot.is_empty() {
+        return "You have no votes".to_string();
+    }
+    let mut current = Vec::new();
+    for (rank, c) in ballot.iter().enumerate() {
+        if args.method.ranked() {
+            current.push(format!("{}. {}", rank + 1, args.choices[*c]));
+        } else if args.method == Method::Score {
+            if !ballot[..rank].contains(c) {
+                let score = ballot.iter().filter(|b| *b == c).count();
+                current.push(format!("{} ({})", args.choices[*c], score));
+            }
+        } else {
+            current.push(args.choices[*c].to_string());
+        }
+    }
+    format!("Your current votes are:\n{}", current.join("\n"))
+}
+
+/// The creator, members who may manage messages or the guild, and configured
+/// moderator roles may end, restart or export a poll.
+fn can_manage(poll: &Poll, user: u64, roles: &[RoleId], permissions: Option<Permissions>) -> bool {
+    user == poll.creator || is_moderator(roles, permissions)
+}
+
+/// Members of prefix commands come without their permissions, so work them out
+/// from the guild roles and the overwrites of the channel.
+async fn member_permissions(ctx: &Context, msg: &Message) -> Option<Permissions> {
+    let guild = msg.guild_id?.to_partial_guild(&ctx.http).await.ok()?;
+    let channel = msg.channel_id.to_channel(&ctx.http).await.ok()?.guild()?;
+    let member = msg.member(ctx).await.ok()?;
+    guild.user_permissions_in(&channel, &member).ok()
+}
+
+fn is_moderator(roles: &[RoleId], permissions: Option<Permissions>) -> bool {
+    permissions.is_some_and(|p| p.manage_messages() || p.manage_guild())
+        || roles
+            .iter()
+            .any(|role| CONFIG.moderators.contains(role.as_u64()))
+}
+
+fn load(id: &u64) -> Result<Option<Votes>> {
+    store::load(Table::Votes, &id.to_string())
+}
+
+fn save(id: &u64, votes: &Votes) -> Result<()> {
+    store::save(Table::Votes, &id.to_string(), votes)
+}
+
+/// Changes the stored votes without losing concurrent changes, starting from
+/// empty votes when there are none yet. `f` may run more than once.
+fn update(
+    store: &dyn PollStore,
+    keys: &Keys,
+    id: &u64,
+    choices: usize,
+    f: impl Fn(Votes) -> Result<Votes>,
+) -> Result<Votes> {
+    store::update(store, keys, Table::Votes, &id.to_string(), |votes| {
+        let votes = match votes {
+            Some(votes) => matching(votes, choices)?,
+            None => Votes::new(choices),
+        };
+        f(votes).map(Some)
+    })?
+    .ok_or_else(|| Error::Corrupted("Votes vanished while updating".to_string()))
+}
+
+/// Votes are kept per choice, so ones stored for a different number of choices
+/// than the poll has can't be counted.
+fn matching(votes: Votes, choices: usize) -> Result<Votes> {
+    if votes.votes.len() != choices {
+        return Err(Error::Corrupted(format!(
+            "Votes for {} choices, but the poll has {}",
+            votes.votes.len(),
+            choices
+        )));
+    }
+    Ok(votes)
+}
+
+fn load_poll(id: &u64) -> Result<Option<Poll>> {
+    store::load(Table::Polls, &id.to_string())
+}
+
+fn save_poll(id: &u64, poll: &Poll) -> Result<()> {
+    store::save(Table::Polls, &id.to_string(), poll)
+}
+
+/// Polls with a render worker, flagged while a newer state waits to be shown.
+static RENDERING: Lazy<Mutex<HashMap<u64, bool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Queues a re-render of the poll message. Changes arriving within the render
+/// window are coalesced, and a poll is edited at most once per render interval,
+/// always with its latest stored state.
+async fn rerender(http: Arc<Http>, msg: Message) {
+    let id = *msg.id.as_u64();
+    {
+        let mut rendering = RENDERING.lock().await;
+        if let Some(pending) = rendering.get_mut(&id) {
+            *pending = true;
+            return;
+        }
+        rendering.insert(id, false);
+    }
+    tokio::spawn(async move {
+        let mut msg = msg;
+        let interval = Duration::from_millis(CONFIG.render.interval);
+        tokio::time::sleep(Duration::from_millis(CONFIG.render.window)).await;
+        loop {
+            let next = tokio::time::Instant::now() + interval;
+            RENDERING.lock().await.insert(id, false);
+            if let Err(why) = render_latest(&http, &mut msg).await {
+                warn!("Failed to render poll {}: {}", id, why);
+            }
+            tokio::time::sleep_until(next).await;
+            let mut rendering = RENDERING.lock().await;
+            if rendering.get(&id) != Some(&true) {
+                rendering.remove(&id);
+                break;
+            }
+        }
+    });
+}
+
+async fn render_latest(http: &Arc<Http>, msg: &mut Message) -> Result<()> {
+    let id = *msg.id.as_u64();
+    match (load_poll(&id)?, load(&id)?) {
+        (Some(poll), Some(votes)) => {
+            let votes = matching(votes, poll.args.choices.len())?;
+            render(http, msg, &poll, &votes).await
+        }
+        // Purged meanwhile
+        _ => Ok(()),
+    }
+}
+
+async fn render(http: &Arc<Http>, msg: &mut Message, poll: &Poll, votes: &Votes) -> Result<()> {
+    let args = &poll.args;
+    let mask = args.mask && !votes.isended;
+
+    let ballots = ballots(votes);
+    let tally = tally(args, &ballots);
+
+    let mut value_vec = Vec::new();
+    if !args.anonymous {
+        for (i, choice) in votes.votes.iter().enumerate() {
+            let mut value = String::new();
+            for (j, detail) in choice.iter().enumerate() {
+                if args.method.ranked() {
+                    let rank = ballots[&detail.id].iter().position(|c| *c == i).unwrap();
+                    value = format!("{}<@{}> #{}\n", value, &detail.id, rank + 1);
+                } else if args.method == Method::Score {
+                    // List every voter once, next to their score
+                    if choice[..j].iter().all(|prev| prev.id != detail.id) {
+                        let score = choice.iter().filter(|d| d.id == detail.id).count();
+                        value = format!("{}<@{}> ({})\n", value, &detail.id, score);
+                    }
+                } else {
+                    value = format!("{}<@{}>\n", value, &detail.id);
+                }
+            }
+            value_vec.push(value);
+        }
+    }
+
+    let mut order: Vec<usize> = (0..args.choices.len()).collect();
+    if args.sort && !mask {
+        order.sort_by_key(|i| std::cmp::Reverse(tally.counts[*i]));
+    }
+    // Bars are relative to the percentage base, or to the leader when there's none
+    let base = match tally.total {
+        0 => tally.counts.iter().copied().max().unwrap_or(0),
+        total => total,
+    };
+
+    let mut chart = None;
+    if votes.isended || (args.chart && !mask) {
+        match chart::bar_chart(&args.title, &args.choices, &tally, votes.isended) {
+            Ok(png) => chart = Some(png),
+            Err(why) => warn!("Failed to draw chart for {}: {}", msg.id, why),
+        }
+    }
+    let has_chart = chart.is_some();
+
+    let author = msg.embeds.first().and_then(|embed| embed.author.clone());
+    let old_charts: Vec<AttachmentId> = msg
+        .attachments
+        .iter()
+        .filter(|attachment| attachment.filename == chart::FILENAME)
+        .map(|attachment| attachment.id)
+        .collect();
+    msg.edit(http, |edit_msg| {
+        // Replace the previous chart, if any
+        for id in old_charts {
+            edit_msg.remove_existing_attachment(id);
+        }
+        if let Some(png) = chart {
+            edit_msg.attachment(AttachmentType::Bytes {
+                data: png.into(),
+                filename: chart::FILENAME.to_string(),
+            });
+        }
+        edit_msg.content(format!(
+            "Total vote(s): {}",
+            votes.votes.iter().map(Vec::len).sum::<usize>()
+        ));
+        edit_msg.embed(|embed| {
+            if let Some(author) = &author {
+                embed.author(|a| {
+                    a.name(&author.name);
+                    if let Some(icon_url) = &author.icon_url {
+                        a.icon_url(icon_url);
+                    }
+                    a
+                });
+            }
+            embed.title(&args.title);
+            let mut text = description(args);
+            if votes.isended {
+                if let Some(outcome) = outcome(args, ballots.len(), &tally) {
+                    text = format!("{}\n\n**{}**", text, outcome);
+                }
+            }
+            // An embed holds 25 fields, so the summary moves below the
+            // description when every field is taken by a choice
+            if args.method != Method::Plurality && votes.isended && args.choices.len() >= 25 {
+                embed.description(format!(
+                    "{}\n\n**{}**\n{}",
+                    text,
+                    args.method.tally_method().name(),
+                    summary(args, &tally)
+                ));
+            } else {
+                embed.description(text);
+            }
+            for i in order {
+                let choice = &args.choices[i];
+                let mut name = choice.to_string();
+                let mut value;
+                if mask {
+                    value = "-".to_string();
+                } else {
+                    value = format!("{}\n", count_line(&tally, i));
+                    if args.bars {
+                        value += &format!("{}\n", progress_bar(tally.counts[i], base));
+                    }
+                    if tally.winners.contains(&i) {
+                        name = format!("{} {}", if votes.isended { "🏆" } else { "⭐" }, name);
+                    }
+                }
+                if !args.anonymous && !mask {
+                    value += &value_vec[i];
+                }
+
+                embed.field(name, value, true);
+            }
+            if args.method != Method::Plurality && votes.isended && args.choices.len() < 25 {
+                embed.field(
+                    args.method.tally_method().name(),
+                    summary(args, &tally),
+                    false,
+                );
+            }
+            if has_chart {
+                embed.attachment(chart::FILENAME);
+            }
+            embed.colour(Colour::ORANGE);
+            embed
+        });
+        edit_msg.components(|f| create_components(f, args, votes.isended));
+        edit_msg
+    })
+    .await?;
+    Ok(())
+}
+
+/// Rebuilds the due schedule of every open poll stored in the DB.
+pub fn start_scheduler(http: Arc<Http>) {
+    let polls = match store::scan::<Poll>(Table::Polls, "") {
+        Ok(polls) => polls,
+        Err(why) => {
+            error!("Failed to load polls: {}", why);
+            return;
+        }
+    };
+    let mut count = 0;
+    for (key, poll) in polls {
+        let (id, poll) = match (key.parse::<u64>(), poll) {
+            (Ok(id), Ok(poll)) => (id, poll),
+            (Err(_), _) => {
+                warn!("Skipping poll with invalid id {}", key);
+                continue;
+            }
+            (_, Err(why)) => {
+                warn!("Skipping unreadable poll {}: {}", key, why);
+                continue;
+            }
+        };
+        if matches!(load(&id), Ok(Some(votes)) if votes.isended) {
+            continue;
+        }
+        schedule(http.clone(), id, poll.args.due);
+        count += 1;
+    }
+    info!("Scheduled {} open poll(s)", count);
+}
+
+/// Closes the poll when its due time passes, even if nobody clicks it.
+fn schedule(http: Arc<Http>, id: u64, due: DateTime<Utc>) {
+    tokio::spawn(async move {
+        let wait = due
+            .signed_duration_since(Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        tokio::time::sleep(wait).await;
+        if let Err(why) = close(&http, id).await {
+            warn!("Failed to close poll {}: {}", id, why);
+        }
+    });
+}
+
+async fn close(http: &Arc<Http>, id: u64) -> Result<()> {
+    let poll = match (load_poll(&id)?, load(&id)?) {
+        (Some(poll), Some(votes)) if !votes.isended => poll,
+        _ => return Ok(()),
+    };
+    let org_msg = http.get_message(poll.channel, id).await?;
+    update(
+        STORE.as_ref(),
+        &KEYS,
+        &id,
+        poll.args.choices.len(),
+        |mut votes| {
+            votes.isended = true;
+            votes.lastupdate = Utc::now();
+            Ok(votes)
+        },
+    )?;
+    rerender(http.clone(), org_msg).await;
+    Ok(())
+}
+
+/// The usage text split into parts which each fit into an embed field.
+pub fn help() -> Vec<String> {
+    fields(&OPTIONS.usage(&format!(
+        "{}vote [options] title choices...\nChoices are written as [emoji] name[|style], style is one of primary, secondary, success, danger",
+        CONFIG.infos.prefix
+    )))
+}
+
+/// Packs whole lines into parts of at most 1024 characters, the limit of an
+/// embed field value.
+fn fields(text: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    for line in text.lines() {
+        let line: String = line.chars().take(1023).collect();
+        let field = fields.last_mut().unwrap();
+        if field.chars().count() + line.chars().count() + 1 > 1024 {
+            fields.push(line);
+        } else {
+            if !field.is_empty() {
+                field.push('\n');
+            }
+            field.push_str(&line);
+        }
+    }
+    fields
+}
+
+/// Purges inactive polls periodically, following the retention config.
+pub fn start_purger() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+            CONFIG.retention.interval.max(1) * 60 * 60,
+        ));
+        loop {
+            interval.tick().await;
+            match purge() {
+                Ok(count) => info!("Purged {} inactive poll(s)", count),
+                Err(why) => error!("Failed to purge polls: {}", why),
+            }
+        }
+    });
+}
+
+fn purge() -> Result<usize> {
+    let retention = &CONFIG.retention;
+    let mut count = 0;
+    for (key, votes) in store::scan::<Votes>(Table::Votes, "")? {
+        let votes = match votes {
+            Ok(votes) => votes,
+            Err(why) => {
+                warn!("Skipping undecodable poll {}: {}", key, why);
+                continue;
+            }
+        };
+        if votes.lastupdate + chrono::Duration::days(retention.days) > Utc::now()
+            || (retention.keep_ended && votes.isended)
+        {
+            continue;
+        }
+        let poll = match key.parse().map(|id| load_poll(&id)) {
+            Ok(Ok(poll)) => poll,
+            _ => {
+                warn!("Skipping poll {} with unreadable definition", key);
+                continue;
+            }
+        };
+        // Polls which can still be voted on are never purged
+        if !votes.isended && poll.as_ref().is_some_and(|p| p.args.due > Utc::now()) {
+            continue;
+        }
+        archive(&key, poll.as_ref(), &votes)?;
+        STORE.delete(Table::Votes, &key)?;
+        STORE.delete(Table::Polls, &key)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn archive(key: &String, poll: Option<&Poll>, votes: &Votes) -> Result<()> {
+    let record = serde_json::json!({ "id": key, "poll": poll, "votes": votes });
+    match CONFIG.retention.archive {
+        Archive::None => Ok(()),
+        Archive::Tree => store::save(Table::Archive, key, &record),
+        Archive::Json => {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&CONFIG.retention.archive_file)?;
+            writeln!(file, "{}", record)?;
+            Ok(())
+        }
+    }
+}
+
+pub fn init() -> Options {
+    let mut options = Options::new();
+
+    options.optopt("d", "description", "set description", "DESCRIPTION");
+    options.optopt(
+        "t",
+        "due",
+        "set due time/date, local to the server timezone",
+        "2h|3d|\"tomorrow 18:00\"|RFC3339",
+    );
+    options.optopt("x", "max", "Max vote", "NUM");
+    options.optflag("a", "anonymous", "anonymous vote");
+    options.optflag("m", "mask", "Mask vote status");
+    options.optflag("n", "noedit", "Disable editing vote");
+    options.optflag("p", "duplicate", "Allow duplicate vote");
+    options.optflag("r", "ranked", "Ranked-choice vote, same as --method irv");
+    options.optflag("s", "select", "Vote with a select menu");
+    options.optflag("c", "chart", "Show a result chart while running");
+    options.optflag("b", "bars", "Show progress bars in the results");
+    options.optflag("o", "sort", "Sort results, leading choice first");
+    options.optopt(
+        "",
+        "method",
+        &format!("Tally method: {}", Method::NAMES),
+        "METHOD",
+    );
+    options.optopt("", "quorum", "Voters needed for the vote to pass", "NUM");
+    options.optopt(
+        "",
+        "threshold",
+        "Share of the votes the winner needs to pass",
+        "2/3|60%",
+    );
+    options.optmulti("", "role", "Only members with ROLE may vote", "ROLE");
+    options.optmulti("", "allow-role", "Same as --role", "ROLE");
+    options.optmulti("", "deny-role", "Members with ROLE may not vote", "ROLE");
+
+    options
+}
+
+fn parser(msg: &str, tz: Tz) -> Result<Args> {
+    let msg_vec = split_args(msg);
+    create(msg_vec.get(1..).unwrap_or_default(), None, tz)
+}
+
+/// Builds the definition of a new poll, which can't be due already. Polls
+/// being migrated may well be overdue, so [`build`] leaves this check out.
+fn create(tokens: &[String], base: Option<&Args>, tz: Tz) -> Result<Args> {
+    let args = build(tokens, base, tz)?;
+    if args.due <= Utc::now() {
+        return Err(Error::user("Due date must be in the future"));
+    }
+    Ok(args)
+}
+
+/// Builds the poll definition from command arguments. Anything the arguments
+/// leave out is taken from `base` when given, e.g. a template. Local due dates
+/// are read in `tz`.
+fn build(tokens: &[String], base: Option<&Args>, tz: Tz) -> Result<Args> {
+    let matches: Matches = match OPTIONS.parse(tokens) {
+        Ok(m) => m,
+        Err(why) => return Err(Error::User(format!("Request parse error: {}", why))),
+    };
+
+    let (title, choices) = match (matches.free.len(), base) {
+        (0, Some(base)) => (base.title.clone(), base.choices.clone()),
+        (1, Some(base)) => (matches.free[0].to_string(), base.choices.clone()),
+        (0..=2, _) => return Err(Error::user("Not enough params")),
+        _ => (
+            matches.free[0].to_string(),
+            matches.free[1..]
+                .iter()
+                .map(|choice| Choice::from(choice.as_str()))
+                .collect::<Vec<Choice>>(),
+        ),
+    };
+    let description = matches
+        .opt_str("d")
+        .or_else(|| base.map(|base| base.description.clone()))
+        .unwrap_or_else(|| "No description".to_string());
+    let d = matches.opt_str("t").unwrap_or_default();
+    let mut due: DateTime<Utc> = match base {
+        Some(base) => base.due,
+        None => Utc::now()
+            .checked_add_signed(chrono::Duration::days(30))
+            .unwrap(),
+    };
+    if !d.is_empty() {
+        due = due::parse(&d, Utc::now(), tz)?;
+    }
+    if choices.len() > MAX_OPTIONS {
+        return Err(Error::user("Too many choices"));
+    }
+    let anonymous = matches.opt_present("a") || base.is_some_and(|base| base.anonymous);
+    let mask = matches.opt_present("m") || base.is_some_and(|base| base.mask);
+    let method = match matches.opt_str("method") {
+        Some(method) => method.parse().map_err(Error::User)?,
+        None if matches.opt_present("r") => Method::Irv,
+        None => base.map_or(Method::Plurality, |base| base.method),
+    };
+    // Max is the score limit per choice for score voting,
+    // other multi-choice methods may mark every choice unless limited
+    let default_max = match method {
+        Method::Plurality => 1,
+        Method::Score => 5,
+        _ => choices.len(),
+    };
+    let max: u8 = match (matches.opt_str("x"), base) {
+        (Some(max), _) => max
+            .parse()
+            .map_err(|_| Error::user("Max vote must be in u8"))?,
+        (None, Some(base)) if base.method == method && base.choices.len() == choices.len() => {
+            base.max
+        }
+        (None, _) => default_max as u8,
+    };
+    let mut editable = !matches.opt_present("n") && base.is_none_or(|base| base.editable);
+    let duplicate = matches.opt_present("p") || base.is_some_and(|base| base.duplicate);
+    if duplicate {
+        if method.ranked() || method == Method::Score {
+            return Err(Error::User(format!(
+                "{} vote can't allow duplicate",
+                method.tally_method().name()
+            )));
+        }
+        editable = false;
+    }
+    // Polls with more choices than buttons fit switch to a select menu
+    let menu = matches.opt_present("s")
+        || base.is_some_and(|base| base.menu)
+        || choices.len() > MAX_BUTTONS;
+    if menu && method == Method::Score {
+        return Err(Error::user("Score vote can't use a select menu"));
+    }
+    let chart = matches.opt_present("c") || base.is_some_and(|base| base.chart);
+    let bars = matches.opt_present("b") || base.is_some_and(|base| base.bars);
+    let sort = matches.opt_present("o") || base.is_some_and(|base| base.sort);
+    let mut allow_roles = Vec::new();
+    for role in matches
+        .opt_strs("role")
+        .iter()
+        .chain(matches.opt_strs("allow-role").iter())
+    {
+        allow_roles.append(&mut role_ids(role)?);
+    }
+    let mut deny_roles = Vec::new();
+    for role in matches.opt_strs("deny-role") {
+        deny_roles.append(&mut role_ids(&role)?);
+    }
+    let quorum = match matches.opt_str("quorum") {
+        Some(quorum) => quorum
+            .parse()
+            .map_err(|_| Error::user("Quorum must be a number of voters"))?,
+        None => base.map_or(0, |base| base.quorum),
+    };
+    let threshold = match matches.opt_str("threshold") {
+        Some(threshold) => Some(parse_ratio(&threshold)?),
+        None => base.and_then(|base| base.threshold),
+    };
+    // Only these tallies count votes a winner can hold a share of
+    if threshold.is_some() && !matches!(method, Method::Plurality | Method::Approval) {
+        return Err(Error::User(format!(
+            "{} vote can't have a threshold",
+            method.tally_method().name()
+        )));
+    }
+    if let Some(base) = base {
+        if allow_roles.is_empty() {
+            allow_roles = base.allow_roles.clone();
+        }
+        if deny_roles.is_empty() {
+            deny_roles = base.deny_roles.clone();
+        }
+    }
+    Ok(Args {
+        title,
+        description,
+        due,
+        choices,
+        anonymous,
+        mask,
+        max,
+        editable,
+        duplicate,
+        method,
+        menu,
+        chart,
+        bars,
+        sort,
+        allow_roles,
+        deny_roles,
+        quorum,
+        threshold,
+    })
+}
+
+/// Accepts comma separated role ids or mentions.
+fn role_ids(roles: &str) -> Result<Vec<u64>> {
+    roles
+        .split(',')
+        .filter(|role| !role.is_empty())
+        .map(|role| {
+            role.trim_start_matches("<@&")
+                .trim_end_matches('>')
+                .parse()
+                .map_err(|_| Error::User(format!("Invalid role: {}", role)))
+        })
+        .collect()
+}
+
+/// Splits on whitespace, keeping "double quoted" words together.
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    args.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        args.push(current);
+    }
+    args
+}
+
+fn quote(s: &str) -> String {
+    if !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        return s.to_string();
+    }
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Checks the user may vote at all.
+fn eligible(args: &Args, roles: &[RoleId]) -> Result<()> {
+    //Due
+    if args.due < Utc::now() {
+        return Err(Error::user("Vote already ended"));
+    }
+
+    //Roles
+    if roles
+        .iter()
+        .any(|role| args.deny_roles.contains(role.as_u64()))
+    {
+        return Err(Error::user("Your roles are not allowed to vote"));
+    }
+    if !args.allow_roles.is_empty()
+        && !roles
+            .iter()
+            .any(|role| args.allow_roles.contains(role.as_u64()))
+    {
+        let allowed: Vec<String> = args
+            .allow_roles
+            .iter()
+            .map(|role| format!("<@&{}>", role))
+            .collect();
+        return Err(Error::User(format!("Only {} may vote", allowed.join(", "))));
+    }
+    Ok(())
+}
+
+fn validator(args: &Args, mut votes: Votes, num: &u8, id: &u64, roles: &[RoleId]) -> Result<Votes> {
+    eligible(args, roles)?;
+
+    //Score: every click raises the score, wrapping back to zero after max
+    if args.method == Method::Score {
+        let score = votes.votes[*num as usize]
+            .iter()
+            .filter(|votedetail| votedetail.id == *id)
+            .count();
+        if score >= args.max as usize {
+            if !args.editable {
+                return Err(Error::user("This vote is not editable"));
+            }
+            votes.votes[*num as usize].retain(|votedetail| votedetail.id != *id);
+        } else {
+            votes.votes[*num as usize].push(VoteDetail {
+                id: *id,
+                time: Utc::now(),
+            });
+        }
+        votes.lastupdate = Utc::now();
+        return Ok(votes);
+    }
+
+    //Editable / Cancel
+    if votes.votes[*num as usize]
+        .iter()
+        .any(|votedetail| votedetail.id == *id)
+    {
+        if args.editable {
+            votes.votes[*num as usize].retain(|votedetail| votedetail.id != *id);
+            return Ok(votes);
+        } else if !args.duplicate {
+            return Err(Error::user("This vote is not editable"));
+        }
+    }
+
+    // Count
+    let mut count = 0;
+    for choice in &votes.votes {
+        for detail in choice {
+            if detail.id == *id {
+                count += 1;
+            }
+        }
+    }
+    if count + 1 > args.max {
+        if args.method.ranked() {
+            return Err(Error::User(format!(
+                "You already ranked {} choice(s)",
+                args.max
+            )));
+        }
+        return Err(Error::user("You already voted"));
+    }
+
+    let id = *id;
+    let time = Utc::now();
+    votes.votes[*num as usize].push(VoteDetail { id, time });
+    votes.lastupdate = Utc::now();
+    Ok(votes)
+}
+
+/// Replaces the user's whole selection with the one submitted from a select menu.
+fn selector(
+    args: &Args,
+    mut votes: Votes,
+    nums: &[u8],
+    id: &u64,
+    roles: &[RoleId],
+) -> Result<Votes> {
+    eligible(args, roles)?;
+
+    if !args.editable && votes.votes.iter().flatten().any(|detail| detail.id == *id) {
+        return Err(Error::user("This vote is not editable"));
+    }
+    if nums.len() > args.max as usize {
+        return Err(Error::User(format!(
+            "You can choose up to {} choice(s)",
+            args.max
+        )));
+    }
+
+    for choice in &mut votes.votes {
+        choice.retain(|votedetail| votedetail.id != *id);
+    }
+    let time = Utc::now();
+    for num in nums {
+        votes.votes[*num as usize].push(VoteDetail { id: *id, time });
+    }
+    votes.lastupdate = time;
+    Ok(votes)
+}
+
+enum Outcome {
+    Passed,
+    Failed,
+    /// Voters and the quorum they missed
+    NoQuorum(usize, usize),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "✅ Passed"),
+            Outcome::Failed => write!(f, "❌ Failed"),
+            Outcome::NoQuorum(voters, quorum) => {
+                write!(f, "⚠️ Quorum not met ({}/{} voters)", voters, quorum)
+            }
+        }
+    }
+}
+
+/// Decides polls with a quorum or threshold, others have no outcome. A vote
+/// passes with a single winner holding at least the threshold of the votes.
+fn outcome(args: &Args, voters: usize, tally: &Tally) -> Option<Outcome> {
+    if args.quorum == 0 && args.threshold.is_none() {
+        return None;
+    }
+    if voters < args.quorum {
+        return Some(Outcome::NoQuorum(voters, args.quorum));
+    }
+    let winner = match tally.winners[..] {
+        [winner] => winner,
+        _ => return Some(Outcome::Failed),
+    };
+    let passed = args
+        .threshold
+        .is_none_or(|(num, den)| tally.counts[winner] * den as usize >= tally.total * num as usize);
+    Some(if passed {
+        Outcome::Passed
+    } else {
+        Outcome::Failed
+    })
+}
+
+/// Reads a fraction like `2/3` or a percentage like `60%`.
+fn parse_ratio(s: &str) -> Result<(u32, u32)> {
+    let parsed = match s.trim().strip_suffix('%') {
+        Some(percent) => percent.trim().parse().ok().map(|num| (num, 100)),
+        None => s
+            .split_once('/')
+            .and_then(|(num, den)| Some((num.trim().parse().ok()?, den.trim().parse().ok()?))),
+    };
+    match parsed {
+        Some((num, den)) if num > 0 && num <= den => Ok((num, den)),
+        _ => Err(Error::user(
+            "Threshold must be a fraction like 2/3 or a percentage like 60%",
+        )),
+    }
+}
+
+fn ratio((num, den): (u32, u32)) -> String {
+    if den == 100 {
+        format!("{}%", num)
+    } else {
+        format!("{}/{}", num, den)
+    }
+}
+
+/// Accepts a bare message id or a message link, whose last segment is the id.
+fn poll_id(poll: &str) -> Option<u64> {
+    poll.trim_end_matches('/').rsplit('/').next()?.parse().ok()
+}
+
+/// Finds a poll by message id or link as seen from `guild` and `channel`. Polls
+/// of other guilds, and outside of guilds those of other channels, aren't found.
+fn lookup(poll: &str, guild: u64, channel: u64) -> Result<(u64, Poll, Votes)> {
+    let id = poll_id(poll).ok_or_else(|| Error::user("Invalid message id or link"))?;
+    let poll = load_poll(&id)?
+        .filter(|poll| match poll.guild {
+            0 => poll.channel == channel,
+            guild_id => guild_id == guild,
+        })
+        .ok_or_else(|| Error::user("Vote not found"))?;
+    let votes = load(&id)?.ok_or_else(|| Error::user("Vote not found"))?;
+    let votes = matching(votes, poll.args.choices.len())?;
+    Ok((id, poll, votes))
+}
+
+fn tally(args: &Args, ballots: &BTreeMap<u64, Ballot>) -> Tally {
+    let names: Vec<String> = args.choices.iter().map(Choice::to_string).collect();
+    args.method
+        .tally_method()
+        .tally(&names, &ballots.values().cloned().collect::<Vec<_>>())
+}
+
+fn count_line(tally: &Tally, i: usize) -> String {
+    match (tally.counts[i] * 100).checked_div(tally.total) {
+        Some(ratio) => format!("**{} {}, {}%**", tally.counts[i], tally.unit, ratio),
+        None => format!("**{} {}**", tally.counts[i], tally.unit),
+    }
+}
+
+fn progress_bar(count: usize, base: usize) -> String {
+    const CELLS: usize = 10;
+    let filled = (count * CELLS + base / 2)
+        .checked_div(base)
+        .unwrap_or(0)
+        .min(CELLS);
+    format!("{}{}", "█".repeat(filled), "░".repeat(CELLS - filled))
+}
+
+/// Orders each voter's choices by the time they were clicked.
+fn ballots(votes: &Votes) -> BTreeMap<u64, Ballot> {
+    let mut clicks: BTreeMap<u64, Vec<(DateTime<Utc>, usize)>> = BTreeMap::new();
+    for (i, choice) in votes.votes.iter().enumerate() {
+        for detail in choice {
+            clicks.entry(detail.id).or_default().push((detail.time, i));
+        }
+    }
+    clicks
+        .into_iter()
+        .map(|(id, mut ranked)| {
+            ranked.sort();
+            (id, ranked.into_iter().map(|(_, i)| i).collect())
+        })
+        .collect()
+}
+
+fn summary(args: &Args, tally: &Tally) -> String {
+    let mut summary = String::new();
+    for note in &tally.notes {
+        summary += &format!("{}\n", note);
+    }
+    let winners: Vec<String> = tally
+        .winners
+        .iter()
+        .map(|c| args.choices[*c].to_string())
+        .collect();
+    if winners.is_empty() {
+        summary += "No winner";
+    } else {
+        summary += &format!("**Winner: {}**", winners.join(", "));
+    }
+    summary.chars().take(1024).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, thread};
+
+    use super::*;
+    use crate::store::{PollStore, SledStore, SqliteStore};
+
+    const THREADS: u64 = 8;
+    const VOTERS: u64 = 25;
+
+    fn click(store: &dyn PollStore, keys: &Keys, args: &Args, user: u64) {
+        update(store, keys, &1, args.choices.len(), |votes| {
+            validator(args, votes, &((user % 2) as u8), &user, &[])
+        })
+        .unwrap();
+    }
+
+    fn vote_concurrently(store: &dyn PollStore) {
+        let keys = Keys::new(&["test".to_string()]);
+        let tokens = ["Lunch", "Pizza", "Sushi"].map(String::from);
+        let args = build(&tokens, None, Tz::UTC).unwrap();
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let (keys, args) = (&keys, &args);
+                scope.spawn(move || {
+                    for voter in 0..VOTERS {
+                        click(store, keys, args, thread * VOTERS + voter);
+                    }
+                });
+            }
+        });
+
+        let value = store.get(Table::Votes, "1").unwrap().unwrap();
+        let value = keys.decrypt(Table::Votes, "1", &value).unwrap();
+        let votes: Votes = serde_json::from_str(&value).unwrap();
+        assert_eq!(ballots(&votes).len() as u64, THREADS * VOTERS);
+        assert_eq!(votes.votes[0].len() as u64, THREADS * VOTERS / 2);
+        assert_eq!(votes.votes[1].len() as u64, THREADS * VOTERS / 2);
+    }
+
+    /// Outcome of a yes/no vote, each ballot a single choice.
+    fn decide(options: &[&str], ballots: &[usize]) -> Option<Outcome> {
+        let tokens: Vec<String> = options
+            .iter()
+            .chain(&["Pass?", "Yes", "No"])
+            .map(|token| token.to_string())
+            .collect();
+        let args = build(&tokens, None, Tz::UTC).unwrap();
+        let ballots: BTreeMap<u64, Ballot> = ballots
+            .iter()
+            .enumerate()
+            .map(|(user, choice)| (user as u64, vec![*choice]))
+            .collect();
+        outcome(&args, ballots.len(), &tally(&args, &ballots))
+    }
+
+    #[test]
+    fn outcomes() {
+        assert!(decide(&[], &[0, 0, 1]).is_none());
+        assert!(matches!(
+            decide(&["--quorum", "3"], &[0, 0]),
+            Some(Outcome::NoQuorum(2, 3))
+        ));
+        assert!(matches!(
+            decide(&["--quorum", "3"], &[0, 0, 1]),
+            Some(Outcome::Passed)
+        ));
+        assert!(matches!(
+            decide(&["--quorum", "2"], &[0, 1]),
+            Some(Outcome::Failed)
+        ));
+        assert!(matches!(
+            decide(&["--threshold", "2/3"], &[0, 0, 1]),
+            Some(Outcome::Passed)
+        ));
+        assert!(matches!(
+            decide(&["--threshold", "2/3"], &[0, 0, 1, 1]),
+            Some(Outcome::Failed)
+        ));
+        assert!(matches!(
+            decide(&["--threshold", "60%"], &[0, 0, 0, 1, 1]),
+            Some(Outcome::Passed)
+        ));
+        assert!(matches!(
+            decide(&["--threshold", "60%"], &[0, 0, 1, 1, 0, 1, 0]),
+            Some(Outcome::Failed)
+        ));
+    }
+
+    #[test]
+    fn ratios() {
+        assert_eq!(parse_ratio("2/3").unwrap(), (2, 3));
+        assert_eq!(parse_ratio("60%").unwrap(), (60, 100));
+        assert_eq!(parse_ratio("3/3").unwrap(), (3, 3));
+        assert!(parse_ratio("0/3").is_err());
+        assert!(parse_ratio("4/3").is_err());
+        assert!(parse_ratio("0%").is_err());
+        assert!(parse_ratio("two thirds").is_err());
+        assert_eq!(ratio((2, 3)), "2/3");
+        assert_eq!(ratio((60, 100)), "60%");
+    }
+
+    #[test]
+    fn help_fits_embed_fields() {
+        let usage = init().usage("~vote [options] title choices...");
+        let fields = fields(&usage);
+        assert!(fields.iter().all(|field| field.chars().count() <= 1024));
+        assert_eq!(fields.join("\n"), usage.trim_end());
+    }
+
+    #[test]
+    fn mismatched_votes_are_corrupted() {
+        let dir = env::temp_dir().join(format!("vote_bot_mismatch_{}", std::process::id()));
+        let store = SledStore::open(&dir.to_string_lossy()).unwrap();
+        let keys = Keys::new(&["test".to_string()]);
+        update(&store, &keys, &1, 2, Ok).unwrap();
+        let result = update(&store, &keys, &1, 3, Ok);
+        assert!(matches!(result, Err(Error::Corrupted(_))));
+        assert!(matches!(
+            matching(Votes::new(2), 3),
+            Err(Error::Corrupted(_))
+        ));
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_votes_are_not_lost() {
+        let dir = env::temp_dir().join(format!("vote_bot_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        vote_concurrently(&SledStore::open(&path("sled")).unwrap());
+        vote_concurrently(&SqliteStore::open(&path("vote.sqlite")).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
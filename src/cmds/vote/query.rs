@@ -0,0 +1,183 @@
+use serenity::{
+    builder::{CreateApplicationCommand, CreateEmbed},
+    model::{
+        application::{
+            command::CommandOptionType,
+            interaction::{
+                application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+                Interaction, InteractionResponseType,
+            },
+        },
+        id::RoleId,
+        Permissions,
+    },
+    prelude::*,
+    utils::Colour,
+};
+use tracing::warn;
+
+use super::{
+    ballots, can_manage, count_line, error_embed, lookup, outcome, summary, tally, Poll, Votes,
+};
+use crate::error::{Error, Result};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("poll")
+        .description("Look up a vote")
+        .dm_permission(false);
+    for (name, description) in [
+        (
+            "results",
+            "Show the current results, unmasked for the owner and moderators",
+        ),
+        ("myvotes", "Show what you voted for"),
+    ] {
+        command.create_option(|option| {
+            option
+                .name(name)
+                .description(description)
+                .kind(CommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("poll")
+                        .description("Message id or link of the vote")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+        });
+    }
+    command
+}
+
+pub async fn application_command(ctx: &Context, i: &Interaction) {
+    if let Interaction::ApplicationCommand(interaction) = i {
+        let result = query(interaction);
+        if let Err(why) = interaction
+            .create_interaction_response(&ctx.http, |res| {
+                res.kind(InteractionResponseType::ChannelMessageWithSource);
+                res.interaction_response_data(|data| {
+                    match result {
+                        Ok(embed) => {
+                            data.add_embed(embed);
+                        }
+                        Err(why) => {
+                            data.embed(|embed| error_embed(embed, &why));
+                        }
+                    }
+                    data.ephemeral(true);
+                    data
+                });
+                res
+            })
+            .await
+        {
+            warn!("Failed to respond to /poll: {}", why);
+        }
+    }
+}
+
+fn query(interaction: &ApplicationCommandInteraction) -> Result<CreateEmbed> {
+    let subcommand = interaction
+        .data
+        .options
+        .first()
+        .ok_or_else(|| Error::user("Not enough params"))?;
+    let poll = subcommand
+        .options
+        .iter()
+        .find_map(|option| match (option.name.as_str(), &option.resolved) {
+            ("poll", Some(CommandDataOptionValue::String(poll))) => Some(poll),
+            _ => None,
+        })
+        .ok_or_else(|| Error::user("Not enough params"))?;
+    let (_, poll, votes) = lookup(
+        poll,
+        interaction.guild_id.map_or(0, |guild| *guild.as_u64()),
+        *interaction.channel_id.as_u64(),
+    )?;
+    let user = *interaction.user.id.as_u64();
+    match subcommand.name.as_str() {
+        "results" => {
+            let (roles, permissions) = match &interaction.member {
+                Some(member) => (member.roles.clone(), member.permissions),
+                None => (Vec::new(), None),
+            };
+            results(&poll, &votes, user, &roles, permissions)
+        }
+        "myvotes" => myvotes(&poll, &votes, user),
+        _ => Err(Error::user("Unknown subcommand")),
+    }
+}
+
+/// Masked results stay hidden until the vote ends, except for those who may manage it.
+fn results(
+    poll: &Poll,
+    votes: &Votes,
+    user: u64,
+    roles: &[RoleId],
+    permissions: Option<Permissions>,
+) -> Result<CreateEmbed> {
+    let args = &poll.args;
+    if args.mask && !votes.isended && !can_manage(poll, user, roles, permissions) {
+        return Err(Error::user("Results of this vote are masked until it ends"));
+    }
+
+    let ballots = ballots(votes);
+    let tally = tally(args, &ballots);
+    let mut embed = CreateEmbed::default();
+    embed.title(&args.title);
+    match outcome(args, ballots.len(), &tally).filter(|_| votes.isended) {
+        Some(outcome) => embed.description(format!("**{}**\n{}", outcome, summary(args, &tally))),
+        None => embed.description(summary(args, &tally)),
+    };
+    for (i, choice) in args.choices.iter().enumerate() {
+        embed.field(choice.to_string(), count_line(&tally, i), true);
+    }
+    embed.footer(|f| {
+        f.text(format!(
+            "{} voter(s), {}",
+            ballots.len(),
+            if votes.isended { "ended" } else { "running" }
+        ));
+        f
+    });
+    embed.colour(Colour::ORANGE);
+    Ok(embed)
+}
+
+fn myvotes(poll: &Poll, votes: &Votes, user: u64) -> Result<CreateEmbed> {
+    let args = &poll.args;
+
+    let mut clicks = Vec::new();
+    for (i, choice) in votes.votes.iter().enumerate() {
+        for detail in choice.iter().filter(|detail| detail.id == user) {
+            clicks.push((detail.time, i));
+        }
+    }
+    clicks.sort();
+    let mut lines = Vec::new();
+    for (n, (time, i)) in clicks.iter().enumerate() {
+        let line = format!("{} <t:{}:f>", args.choices[*i], time.timestamp());
+        if args.method.ranked() {
+            lines.push(format!("{}. {}", n + 1, line));
+        } else {
+            lines.push(line);
+        }
+    }
+
+    let mut embed = CreateEmbed::default();
+    embed.title(&args.title);
+    if lines.is_empty() {
+        embed.description("You have no votes");
+    } else {
+        embed.description(
+            format!("Your current votes are:\n{}", lines.join("\n"))
+                .chars()
+                .take(4096)
+                .collect::<String>(),
+        );
+    }
+    embed.colour(Colour::ORANGE);
+    Ok(embed)
+}
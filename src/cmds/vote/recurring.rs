@@ -0,0 +1,463 @@
+use std::{str::FromStr, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    builder::CreateApplicationCommand,
+    framework::standard::{macros::command, CommandResult},
+    http::Http,
+    model::{
+        application::{command::CommandOptionType, interaction::Interaction},
+        channel::Message,
+        id::ChannelId,
+    },
+    prelude::*,
+};
+use tracing::{info, warn};
+
+use super::{
+    create, create_components, create_embed, due, is_moderator, member_permissions, open, reply,
+    result_embed, split_args, string_option, Args,
+};
+use crate::{
+    config,
+    crypto::KEYS,
+    error::{Error, Result},
+    store::{self, Table, STORE},
+};
+
+#[derive(Serialize, Deserialize)]
+struct Recurring {
+    args: Args,
+    /// Seconds from posting until each vote is due
+    duration: i64,
+    /// Interval such as `1d`, or a cron expression optionally followed by a timezone
+    when: String,
+    channel: u64,
+    guild: u64,
+    creator: u64,
+    next: DateTime<Utc>,
+}
+
+#[command]
+async fn schedule(ctx: &Context, msg: &Message) -> CommandResult {
+    let tokens = split_args(&msg.content);
+    let roles = msg.member.as_ref().map_or(Vec::new(), |m| m.roles.clone());
+    let result = manage(
+        &ctx.http,
+        *msg.channel_id.as_u64(),
+        msg.guild_id.map_or(0, |guild| *guild.as_u64()),
+        *msg.author.id.as_u64(),
+        is_moderator(&roles, member_permissions(ctx, msg).await),
+        tokens.get(1).map_or("", String::as_str),
+        tokens.get(2..).unwrap_or_default(),
+    );
+    msg.channel_id
+        .send_message(&ctx.http, |msg_res| {
+            msg_res.embed(|embed| result_embed(embed, "Schedule", result));
+            msg_res.reference_message(msg);
+            msg_res
+        })
+        .await?;
+    Ok(())
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("schedule")
+        .description("Post a vote into this channel on a schedule");
+    command.create_option(|option| {
+        option
+            .name("add")
+            .description("Schedule a recurring vote")
+            .kind(CommandOptionType::SubCommand)
+            .create_sub_option(|option| {
+                option
+                    .name("when")
+                    .description("Interval like 1d, or cron like \"0 9 * * Mon Asia/Tokyo\"")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+            .create_sub_option(|option| {
+                option
+                    .name("args")
+                    .description("Options, title and choices, as for the vote command")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    });
+    command.create_option(|option| {
+        option
+            .name("list")
+            .description("List the recurring votes of this server")
+            .kind(CommandOptionType::SubCommand)
+    });
+    command.create_option(|option| {
+        option
+            .name("cancel")
+            .description("Cancel a recurring vote")
+            .kind(CommandOptionType::SubCommand)
+            .create_sub_option(|option| {
+                option
+                    .name("id")
+                    .description("Id of the recurring vote")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+    });
+    command
+}
+
+pub async fn application_command(ctx: &Context, i: &Interaction) {
+    if let Interaction::ApplicationCommand(interaction) = i {
+        let (subcommand, options) = match interaction.data.options.first() {
+            Some(subcommand) => (subcommand.name.as_str(), &subcommand.options[..]),
+            None => ("", &[][..]),
+        };
+        let mut tokens = Vec::new();
+        tokens.extend(string_option(options, "when"));
+        tokens.extend(string_option(options, "id"));
+        tokens.extend(split_args(
+            &string_option(options, "args").unwrap_or_default(),
+        ));
+        let (roles, permissions) = match &interaction.member {
+            Some(member) => (member.roles.clone(), member.permissions),
+            None => (Vec::new(), None),
+        };
+        let result = manage(
+            &ctx.http,
+            *interaction.channel_id.as_u64(),
+            interaction.guild_id.map_or(0, |guild| *guild.as_u64()),
+            *interaction.user.id.as_u64(),
+            is_moderator(&roles, permissions),
+            subcommand,
+            &tokens,
+        );
+        reply(ctx, interaction, "Schedule", result).await;
+    }
+}
+
+/// Runs the add, list and cancel subcommands.
+fn manage(
+    http: &Arc<Http>,
+    channel: u64,
+    guild: u64,
+    user: u64,
+    moderator: bool,
+    subcommand: &str,
+    tokens: &[String],
+) -> Result<String> {
+    if guild == 0 {
+        return Err(Error::user("Schedules are only available in servers"));
+    }
+    match subcommand {
+        "add" => {
+            let when = tokens
+                .first()
+                .ok_or_else(|| Error::user("Not enough params"))?;
+            let args = create(&tokens[1..], None, config::timezone(guild))?;
+            let next = next(when, Utc::now(), config::timezone(guild))?;
+            let recurring = Recurring {
+                duration: (args.due - Utc::now()).num_seconds().max(0),
+                args,
+                when: when.to_string(),
+                channel,
+                guild,
+                creator: user,
+                next,
+            };
+            let id = STORE.generate_id()?;
+            save(&id, &recurring)?;
+            run(http.clone(), id);
+            Ok(format!(
+                "Scheduled `{}`, first vote <t:{}:R>",
+                id,
+                next.timestamp()
+            ))
+        }
+        "list" => {
+            let mut list = Vec::new();
+            for (key, recurring) in store::scan::<Recurring>(Table::Schedules, "")? {
+                let recurring = recurring?;
+                if recurring.guild == guild {
+                    list.push(format!(
+                        "`{}` {}: {} in <#{}>, next <t:{}:R>",
+                        key,
+                        recurring.when,
+                        recurring.args.title,
+                        recurring.channel,
+                        recurring.next.timestamp()
+                    ));
+                }
+            }
+            if list.is_empty() {
+                return Ok("No recurring votes".to_string());
+            }
+            Ok(list.join("\n").chars().take(4096).collect())
+        }
+        "cancel" => {
+            let id = tokens
+                .first()
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| Error::user("Invalid id"))?;
+            let recurring = load(&id)?
+                .filter(|recurring| recurring.guild == guild)
+                .ok_or_else(|| Error::user("Schedule not found"))?;
+            if recurring.creator != user && !moderator {
+                return Err(Error::user(
+                    "Only the owner or moderators can cancel this schedule",
+                ));
+            }
+            STORE.delete(Table::Schedules, &id.to_string())?;
+            Ok(format!("Cancelled `{}`", id))
+        }
+        _ => Err(Error::user("Use add, list or cancel")),
+    }
+}
+
+/// Intervals are written as durations like `1d` or `1d12h`. Anything else is a
+/// cron expression of 5 fields (minute to weekday), or 6 with seconds first,
+/// evaluated in the timezone which may follow it, `tz` otherwise.
+fn next(when: &str, after: DateTime<Utc>, tz: Tz) -> Result<DateTime<Utc>> {
+    let when = when.trim();
+    if let Some(interval) = due::duration(when) {
+        if interval < chrono::Duration::minutes(10) {
+            return Err(Error::user("Interval must be at least 10 minutes"));
+        }
+        return after
+            .checked_add_signed(interval)
+            .ok_or_else(|| Error::user("Interval is too long"));
+    }
+
+    let mut fields: Vec<&str> = when.split_whitespace().collect();
+    let tz = match fields.last().map(|field| field.parse::<Tz>()) {
+        Some(Ok(tz)) => {
+            fields.pop();
+            tz
+        }
+        _ => tz,
+    };
+    let mut fields: Vec<String> = fields.into_iter().map(str::to_string).collect();
+    match fields.len() {
+        5 => fields.insert(0, "0".to_string()),
+        6 => {}
+        _ => return Err(Error::user("Cron expression must have 5 or 6 fields")),
+    }
+    fields[5] = weekdays(&fields[5])?;
+    let expression = fields.join(" ");
+    let schedule = cron::Schedule::from_str(&expression)
+        .map_err(|why| Error::User(format!("Cron parse error: {}", why)))?;
+    schedule
+        .after(&after.with_timezone(&tz))
+        .next()
+        .map(|next| next.with_timezone(&Utc))
+        .ok_or_else(|| Error::user("Schedule never fires"))
+}
+
+/// Cron numbers weekdays from 0 (or 7) for Sunday, but the cron crate from 1,
+/// so numeric days, ranges and steps are spelled out in its numbering. Names
+/// are passed through.
+fn weekdays(field: &str) -> Result<String> {
+    let day = |s: &str| match s.parse::<usize>() {
+        Ok(day) if day <= 7 => Ok(day),
+        _ => Err(Error::User(format!("Invalid weekday {}", s))),
+    };
+    let mut days: Vec<String> = Vec::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        if !range.starts_with(|c: char| c.is_ascii_digit()) {
+            days.push(item.to_string());
+            continue;
+        }
+        let (from, to) = range.split_once('-').unwrap_or((range, range));
+        let (from, to) = (day(from)?, day(to)?);
+        let step = match step {
+            Some(step) => step
+                .parse()
+                .ok()
+                .filter(|step| *step > 0)
+                .ok_or_else(|| Error::User(format!("Invalid weekday step {}", step)))?,
+            None => 1,
+        };
+        if from > to {
+            return Err(Error::User(format!("Invalid weekday range {}", range)));
+        }
+        for day in (from..=to).step_by(step) {
+            let day = (day % 7 + 1).to_string();
+            if !days.contains(&day) {
+                days.push(day);
+            }
+        }
+    }
+    Ok(days.join(","))
+}
+
+/// Restarts every stored schedule.
+pub fn start_recurring(http: Arc<Http>) {
+    let schedules = match STORE.scan(Table::Schedules, "") {
+        Ok(schedules) => schedules,
+        Err(why) => {
+            warn!("Failed to load schedules: {}", why);
+            return;
+        }
+    };
+    let mut count = 0;
+    for (key, _) in schedules {
+        match key.parse() {
+            Ok(id) => {
+                run(http.clone(), id);
+                count += 1;
+            }
+            _ => warn!("Skipping unreadable schedule"),
+        }
+    }
+    info!("Started {} recurring vote(s)", count);
+}
+
+/// Posts the vote every time the schedule fires, until it is cancelled.
+fn run(http: Arc<Http>, id: u64) {
+    tokio::spawn(async move {
+        loop {
+            let fires = match load(&id) {
+                Ok(Some(recurring)) => recurring.next,
+                Ok(None) => break,
+                Err(why) => {
+                    warn!("Failed to load schedule {}: {}", id, why);
+                    break;
+                }
+            };
+            let wait = fires
+                .signed_duration_since(Utc::now())
+                .to_std()
+                .unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            // Cancelled meanwhile
+            let recurring = match load(&id) {
+                Ok(Some(recurring)) => recurring,
+                _ => break,
+            };
+            if let Err(why) = post(&http, &recurring).await {
+                warn!("Failed to post scheduled vote {}: {}", id, why);
+            }
+            let next = match next(
+                &recurring.when,
+                recurring.next.max(Utc::now()),
+                config::timezone(recurring.guild),
+            ) {
+                Ok(next) => next,
+                Err(why) => {
+                    warn!("Dropping schedule {}: {}", id, why);
+                    if let Err(why) = STORE.delete(Table::Schedules, &id.to_string()) {
+                        warn!("Failed to remove schedule {}: {}", id, why);
+                    }
+                    break;
+                }
+            };
+            // Cancelled while posting
+            match reschedule(&id, next) {
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(why) => {
+                    warn!("Failed to save schedule {}: {}", id, why);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+async fn post(http: &Arc<Http>, recurring: &Recurring) -> Result<()> {
+    let mut args = recurring.args.clone();
+    args.due = Utc::now() + chrono::Duration::seconds(recurring.duration);
+    let creator = http.get_user(recurring.creator).await?;
+    let sent = ChannelId(recurring.channel)
+        .send_message(http, |msg| {
+            msg.embed(|embed| create_embed(embed, &creator, &args));
+            msg.components(|f| create_components(f, &args, false));
+            msg
+        })
+        .await?;
+    open(http, &sent, args, &creator, recurring.guild)
+}
+
+fn load(id: &u64) -> Result<Option<Recurring>> {
+    store::load(Table::Schedules, &id.to_string())
+}
+
+/// Moves the schedule to its next firing without bringing it back once
+/// cancelled, in which case `None` is returned.
+fn reschedule(id: &u64, next: DateTime<Utc>) -> Result<Option<Recurring>> {
+    store::update(
+        STORE.as_ref(),
+        &KEYS,
+        Table::Schedules,
+        &id.to_string(),
+        |recurring| {
+            Ok(recurring.map(|mut recurring: Recurring| {
+                recurring.next = next;
+                recurring
+            }))
+        },
+    )
+}
+
+fn save(id: &u64, recurring: &Recurring) -> Result<()> {
+    store::save(Table::Schedules, &id.to_string(), recurring)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    // Sunday
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 2, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn numeric_weekdays_count_from_sunday() {
+        let at = |when| next(when, now(), Tz::UTC).unwrap();
+        assert_eq!(
+            at("0 9 * * 1"),
+            Utc.with_ymd_and_hms(2024, 6, 3, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at("0 13 * * 0"),
+            Utc.with_ymd_and_hms(2024, 6, 2, 13, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at("0 9 * * 7"),
+            Utc.with_ymd_and_hms(2024, 6, 9, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at("0 9 * * 6"),
+            Utc.with_ymd_and_hms(2024, 6, 8, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at("0 9 * * 3-5"),
+            Utc.with_ymd_and_hms(2024, 6, 5, 9, 0, 0).unwrap()
+        );
+        assert_eq!(at("0 9 * * Mon"), at("0 9 * * 1"));
+        assert_eq!(at("0 0 9 * * 1"), at("0 9 * * 1"));
+    }
+
+    #[test]
+    fn weekday_fields() {
+        assert_eq!(weekdays("0").unwrap(), "1");
+        assert_eq!(weekdays("1-5").unwrap(), "2,3,4,5,6");
+        assert_eq!(weekdays("5-7").unwrap(), "6,7,1");
+        assert_eq!(weekdays("0,7").unwrap(), "1");
+        assert_eq!(weekdays("0-6/2").unwrap(), "1,3,5,7");
+        assert_eq!(weekdays("*").unwrap(), "*");
+        assert_eq!(weekdays("Mon-Fri").unwrap(), "Mon-Fri");
+        assert!(weekdays("8").is_err());
+        assert!(weekdays("5-1").is_err());
+        assert!(weekdays("1-5/0").is_err());
+    }
+}
@@ -0,0 +1,494 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// A ballot lists the choices one voter clicked, oldest first.
+pub type Ballot = Vec<usize>;
+
+pub struct Tally {
+    /// Figure shown next to every choice
+    pub counts: Vec<usize>,
+    /// Base of the percentage, 0 hides it
+    pub total: usize,
+    pub unit: &'static str,
+    pub winners: Vec<usize>,
+    /// Extra lines explaining how the winners were found
+    pub notes: Vec<String>,
+}
+
+pub trait TallyMethod {
+    fn name(&self) -> &'static str;
+    fn tally(&self, choices: &[String], ballots: &[Ballot]) -> Tally;
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    #[default]
+    Plurality,
+    Approval,
+    Borda,
+    Schulze,
+    Score,
+    Irv,
+}
+
+impl Method {
+    pub const NAMES: &'static str = "plurality, approval, borda, schulze, score, irv";
+
+    /// Whether the click order of a ballot matters.
+    pub fn ranked(&self) -> bool {
+        matches!(self, Method::Borda | Method::Schulze | Method::Irv)
+    }
+
+    pub fn tally_method(&self) -> &'static dyn TallyMethod {
+        match self {
+            Method::Plurality => &Plurality,
+            Method::Approval => &Approval,
+            Method::Borda => &Borda,
+            Method::Schulze => &Schulze,
+            Method::Score => &Score,
+            Method::Irv => &InstantRunoff,
+        }
+    }
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "plurality" => Ok(Method::Plurality),
+            "approval" => Ok(Method::Approval),
+            "borda" => Ok(Method::Borda),
+            "schulze" | "condorcet" => Ok(Method::Schulze),
+            "score" | "range" => Ok(Method::Score),
+            "irv" | "ranked" => Ok(Method::Irv),
+            _ => Err(format!(
+                "Unknown method {}, use one of {}",
+                s,
+                Method::NAMES
+            )),
+        }
+    }
+}
+
+/// Drops repeated clicks, keeping the first position of every choice.
+fn ranking(ballot: &Ballot) -> Vec<usize> {
+    let mut ranking = Vec::new();
+    for c in ballot {
+        if !ranking.contains(c) {
+            ranking.push(*c);
+        }
+    }
+    ranking
+}
+
+fn top(counts: &[usize]) -> Vec<usize> {
+    let max = counts.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return Vec::new();
+    }
+    (0..counts.len()).filter(|c| counts[*c] == max).collect()
+}
+
+/// Every click counts as one vote.
+pub struct Plurality;
+
+impl TallyMethod for Plurality {
+    fn name(&self) -> &'static str {
+        "Plurality"
+    }
+
+    fn tally(&self, choices: &[String], ballots: &[Ballot]) -> Tally {
+        let mut counts = vec![0; choices.len()];
+        for c in ballots.iter().flatten() {
+            counts[*c] += 1;
+        }
+        Tally {
+            total: counts.iter().sum(),
+            winners: top(&counts),
+            counts,
+            unit: "people(s)",
+            notes: Vec::new(),
+        }
+    }
+}
+
+/// Every voter approves any number of choices, shown against the number of voters.
+pub struct Approval;
+
+impl TallyMethod for Approval {
+    fn name(&self) -> &'static str {
+        "Approval"
+    }
+
+    fn tally(&self, choices: &[String], ballots: &[Ballot]) -> Tally {
+        let mut counts = vec![0; choices.len()];
+        for ballot in ballots {
+            for c in ranking(ballot) {
+                counts[c] += 1;
+            }
+        }
+        Tally {
+            total: ballots.len(),
+            winners: top(&counts),
+            counts,
+            unit: "people(s)",
+            notes: Vec::new(),
+        }
+    }
+}
+
+/// The n-th ranked of m choices earns m - n points, unranked choices earn nothing.
+pub struct Borda;
+
+impl TallyMethod for Borda {
+    fn name(&self) -> &'static str {
+        "Borda count"
+    }
+
+    fn tally(&self, choices: &[String], ballots: &[Ballot]) -> Tally {
+        let mut counts = vec![0; choices.len()];
+        for ballot in ballots {
+            for (rank, c) in ranking(ballot).into_iter().enumerate() {
+                counts[c] += choices.len() - 1 - rank;
+            }
+        }
+        Tally {
+            total: counts.iter().sum(),
+            winners: top(&counts),
+            counts,
+            unit: "point(s)",
+            notes: Vec::new(),
+        }
+    }
+}
+
+/// Beatpath method: a choice wins when its strongest path to every other choice
+/// is at least as strong as the reverse. Ranked choices beat unranked ones.
+pub struct Schulze;
+
+impl Schulze {
+    /// `d[i][j]` is the number of voters preferring i over j.
+    pub fn preferences(choices: usize, ballots: &[Ballot]) -> Vec<Vec<usize>> {
+        let mut d = vec![vec![0; choices]; choices];
+        for ballot in ballots {
+            let ranking = ranking(ballot);
+            for (rank, i) in ranking.iter().enumerate() {
+                for (j, count) in d[*i].iter_mut().enumerate() {
+                    if *i != j && !ranking[..rank].contains(&j) {
+                        *count += 1;
+                    }
+                }
+            }
+        }
+        d
+    }
+
+    /// `p[i][j]` is the strength of the strongest path from i to j.
+    pub fn paths(d: &[Vec<usize>]) -> Vec<Vec<usize>> {
+        let n = d.len();
+        let mut p = vec![vec![0; n]; n];
+        for i in 0..n {
+            for j in 0..n {
+                if i != j && d[i][j] > d[j][i] {
+                    p[i][j] = d[i][j];
+                }
+            }
+        }
+        for k in 0..n {
+            for i in 0..n {
+                if i == k {
+                    continue;
+                }
+                for j in 0..n {
+                    if j != k && j != i {
+                        p[i][j] = p[i][j].max(p[i][k].min(p[k][j]));
+                    }
+                }
+            }
+        }
+        p
+    }
+}
+
+impl TallyMethod for Schulze {
+    fn name(&self) -> &'static str {
+        "Schulze"
+    }
+
+    fn tally(&self, choices: &[String], ballots: &[Ballot]) -> Tally {
+        let n = choices.len();
+        let d = Schulze::preferences(n, ballots);
+        let p = Schulze::paths(&d);
+        let counts: Vec<usize> = (0..n)
+            .map(|i| (0..n).filter(|j| i != *j && p[i][*j] > p[*j][i]).count())
+            .collect();
+        let mut winners = Vec::new();
+        if ballots.iter().any(|ballot| !ballot.is_empty()) {
+            winners = (0..n)
+                .filter(|i| (0..n).all(|j| *i == j || p[*i][j] >= p[j][*i]))
+                .collect();
+        }
+        let mut notes = Vec::new();
+        if let [winner] = winners[..] {
+            if (0..n).all(|j| winner == j || d[winner][j] > d[j][winner]) {
+                notes.push(format!("{} is the Condorcet winner", choices[winner]));
+            }
+        }
+        Tally {
+            total: 0,
+            counts,
+            unit: "pairwise win(s)",
+            winners,
+            notes,
+        }
+    }
+}
+
+/// Clicking a choice repeatedly raises its score, the highest total score wins.
+pub struct Score;
+
+impl TallyMethod for Score {
+    fn name(&self) -> &'static str {
+        "Score"
+    }
+
+    fn tally(&self, choices: &[String], ballots: &[Ballot]) -> Tally {
+        let mut counts = vec![0; choices.len()];
+        for c in ballots.iter().flatten() {
+            counts[*c] += 1;
+        }
+        let mut notes = Vec::new();
+        if !ballots.is_empty() {
+            for (c, choice) in choices.iter().enumerate() {
+                notes.push(format!(
+                    "{}: average {:.2}",
+                    choice,
+                    counts[c] as f64 / ballots.len() as f64
+                ));
+            }
+        }
+        Tally {
+            total: 0,
+            winners: top(&counts),
+            counts,
+            unit: "point(s)",
+            notes,
+        }
+    }
+}
+
+/// Eliminates the weakest choices round by round until one holds a majority
+/// of the ballots still in play. Choices tied for last are eliminated together;
+/// if every remaining choice is tied, they all win.
+pub struct InstantRunoff;
+
+impl TallyMethod for InstantRunoff {
+    fn name(&self) -> &'static str {
+        "Instant-runoff"
+    }
+
+    fn tally(&self, choices: &[String], ballots: &[Ballot]) -> Tally {
+        let n = choices.len();
+        let mut remaining = vec![true; n];
+        let mut first_round = None;
+        let mut notes = Vec::new();
+        let winners;
+        let mut round = 1;
+        loop {
+            let mut counts = vec![0; n];
+            for ballot in ballots {
+                if let Some(first) = ballot.iter().find(|c| remaining[**c]) {
+                    counts[*first] += 1;
+                }
+            }
+            if first_round.is_none() {
+                first_round = Some(counts.clone());
+            }
+            let active: usize = counts.iter().sum();
+            let alive: Vec<usize> = (0..n).filter(|c| remaining[*c]).collect();
+            let top = alive.iter().map(|c| counts[*c]).max().unwrap_or(0);
+            let bottom = alive.iter().map(|c| counts[*c]).min().unwrap_or(0);
+            if active == 0 {
+                winners = Vec::new();
+                break;
+            }
+            if top * 2 > active || top == bottom {
+                winners = alive
+                    .into_iter()
+                    .filter(|c| counts[*c] == top)
+                    .collect::<Vec<usize>>();
+                let standing: Vec<String> = winners
+                    .iter()
+                    .map(|c| format!("{} ({}/{})", choices[*c], counts[*c], active))
+                    .collect();
+                notes.push(format!("Round {}: {}", round, standing.join(", ")));
+                break;
+            }
+            let eliminated: Vec<String> = alive
+                .into_iter()
+                .filter(|c| counts[*c] == bottom)
+                .map(|c| {
+                    remaining[c] = false;
+                    format!("{} ({})", choices[c], counts[c])
+                })
+                .collect();
+            notes.push(format!(
+                "Round {}: eliminated {}",
+                round,
+                eliminated.join(", ")
+            ));
+            round += 1;
+        }
+        Tally {
+            total: ballots.len(),
+            counts: first_round.unwrap_or_default(),
+            unit: "people(s)",
+            winners,
+            notes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METHODS: [Method; 6] = [
+        Method::Plurality,
+        Method::Approval,
+        Method::Borda,
+        Method::Schulze,
+        Method::Score,
+        Method::Irv,
+    ];
+
+    fn choices(n: usize) -> Vec<String> {
+        ["A", "B", "C", "D", "E"][..n]
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// `count` voters casting the same ballot.
+    fn ballots(groups: &[(usize, &[usize])]) -> Vec<Ballot> {
+        groups
+            .iter()
+            .flat_map(|(count, ballot)| vec![ballot.to_vec(); *count])
+            .collect()
+    }
+
+    #[test]
+    fn plurality_counts_every_click() {
+        let tally = Plurality.tally(&choices(3), &ballots(&[(2, &[0]), (1, &[1, 2])]));
+        assert_eq!(tally.counts, vec![2, 1, 1]);
+        assert_eq!(tally.total, 4);
+        assert_eq!(tally.winners, vec![0]);
+    }
+
+    #[test]
+    fn approval_counts_voters_once_per_choice() {
+        let tally = Approval.tally(
+            &choices(3),
+            &ballots(&[(1, &[0, 1]), (1, &[1]), (1, &[1, 1, 2])]),
+        );
+        assert_eq!(tally.counts, vec![1, 3, 1]);
+        assert_eq!(tally.total, 3);
+        assert_eq!(tally.winners, vec![1]);
+    }
+
+    #[test]
+    fn borda_scores_partial_rankings() {
+        let tally = Borda.tally(
+            &choices(3),
+            &ballots(&[(1, &[0, 1, 2]), (1, &[1]), (1, &[2, 0]), (1, &[0, 0])]),
+        );
+        assert_eq!(tally.counts, vec![5, 3, 2]);
+        assert_eq!(tally.winners, vec![0]);
+    }
+
+    #[test]
+    fn schulze_breaks_a_cycle_by_beatpath() {
+        // A beats B 6:3, B beats C 7:2 and C beats A 5:4
+        let ballots = ballots(&[(4, &[0, 1, 2]), (3, &[1, 2, 0]), (2, &[2, 0, 1])]);
+        let d = Schulze::preferences(3, &ballots);
+        assert_eq!(d, vec![vec![0, 6, 4], vec![3, 0, 7], vec![5, 2, 0]]);
+        let p = Schulze::paths(&d);
+        assert_eq!(p, vec![vec![0, 6, 6], vec![5, 0, 7], vec![5, 5, 0]]);
+
+        let tally = Schulze.tally(&choices(3), &ballots);
+        assert_eq!(tally.counts, vec![2, 1, 0]);
+        assert_eq!(tally.winners, vec![0]);
+        assert!(tally.notes.is_empty());
+    }
+
+    #[test]
+    fn schulze_names_the_condorcet_winner() {
+        let tally = Schulze.tally(&choices(3), &ballots(&[(2, &[1, 0]), (1, &[0, 1, 2])]));
+        assert_eq!(tally.winners, vec![1]);
+        assert_eq!(tally.notes, vec!["B is the Condorcet winner"]);
+    }
+
+    #[test]
+    fn score_sums_repeated_clicks() {
+        let tally = Score.tally(&choices(2), &ballots(&[(1, &[0, 0, 1]), (1, &[1, 1, 1])]));
+        assert_eq!(tally.counts, vec![2, 4]);
+        assert_eq!(tally.winners, vec![1]);
+        assert_eq!(tally.notes, vec!["A: average 1.00", "B: average 2.00"]);
+    }
+
+    #[test]
+    fn irv_eliminates_ties_for_last_together() {
+        let tally = InstantRunoff.tally(
+            &choices(5),
+            &ballots(&[
+                (5, &[0]),
+                (4, &[1, 2]),
+                (3, &[2]),
+                (1, &[3, 2]),
+                (1, &[4, 2]),
+            ]),
+        );
+        assert_eq!(tally.counts, vec![5, 4, 3, 1, 1]);
+        assert_eq!(tally.total, 14);
+        assert_eq!(tally.winners, vec![2]);
+        assert_eq!(
+            tally.notes,
+            vec![
+                "Round 1: eliminated D (1), E (1)",
+                "Round 2: eliminated B (4)",
+                "Round 3: C (9/14)",
+            ]
+        );
+    }
+
+    #[test]
+    fn irv_shares_the_win_when_all_remaining_tie() {
+        let tally = InstantRunoff.tally(&choices(2), &ballots(&[(1, &[0]), (1, &[1])]));
+        assert_eq!(tally.winners, vec![0, 1]);
+        assert_eq!(tally.notes, vec!["Round 1: A (1/2), B (1/2)"]);
+    }
+
+    #[test]
+    fn no_ballots_have_no_winner() {
+        for method in METHODS {
+            let tally = method.tally_method().tally(&choices(3), &[]);
+            assert_eq!(tally.counts, vec![0, 0, 0], "{:?}", method);
+            assert!(tally.winners.is_empty(), "{:?}", method);
+            assert!(tally.notes.is_empty(), "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn empty_ballots_have_no_winner() {
+        for method in METHODS {
+            let tally = method
+                .tally_method()
+                .tally(&choices(3), &[Vec::new(), Vec::new()]);
+            assert_eq!(tally.counts, vec![0, 0, 0], "{:?}", method);
+            assert!(tally.winners.is_empty(), "{:?}", method);
+        }
+    }
+}
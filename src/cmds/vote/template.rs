@@ -0,0 +1,220 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    builder::CreateApplicationCommand,
+    framework::standard::{macros::command, CommandResult},
+    model::{
+        application::{command::CommandOptionType, interaction::Interaction},
+        channel::Message,
+        id::RoleId,
+        Permissions,
+    },
+    prelude::*,
+};
+
+use super::{
+    create, is_moderator, member_permissions, post, reply, respond, result_embed, split_args,
+    string_option, Args,
+};
+use crate::{
+    config,
+    error::{Error, Result},
+    store::{self, Table, STORE},
+};
+
+#[derive(Serialize, Deserialize)]
+struct Template {
+    args: Args,
+    /// Seconds from launch until the vote is due
+    duration: i64,
+    creator: u64,
+}
+
+#[command]
+async fn template(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild_id.map_or(0, |guild| *guild.as_u64());
+    let tokens = split_args(&msg.content);
+    if tokens.get(1).map(String::as_str) == Some("use") {
+        let parsed = match tokens.get(2) {
+            Some(name) => launch(guild, name, &tokens[3..]),
+            None => Err(Error::user("Not enough params")),
+        };
+        return post(ctx, msg, parsed).await;
+    }
+
+    let roles = msg.member.as_ref().map_or(Vec::new(), |m| m.roles.clone());
+    let result = manage(
+        guild,
+        *msg.author.id.as_u64(),
+        &roles,
+        member_permissions(ctx, msg).await,
+        tokens.get(1).map_or("", String::as_str),
+        tokens.get(2).map_or("", String::as_str),
+        tokens.get(3..).unwrap_or_default(),
+    );
+    msg.channel_id
+        .send_message(&ctx.http, |msg_res| {
+            msg_res.embed(|embed| result_embed(embed, "Template", result));
+            msg_res.reference_message(msg);
+            msg_res
+        })
+        .await?;
+    Ok(())
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("template")
+        .description("Save and launch votes from templates of this server");
+    for (name, description, args) in [
+        ("save", "Save a template", Some(true)),
+        ("use", "Start a vote from a template", Some(false)),
+        ("list", "List the templates", None),
+        ("delete", "Delete a template", None),
+    ] {
+        command.create_option(|option| {
+            option
+                .name(name)
+                .description(description)
+                .kind(CommandOptionType::SubCommand);
+            if name != "list" {
+                option.create_sub_option(|option| {
+                    option
+                        .name("name")
+                        .description("Name of the template")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                });
+            }
+            if let Some(required) = args {
+                option.create_sub_option(|option| {
+                    option
+                        .name("args")
+                        .description("Options, title and choices, as for the vote command")
+                        .kind(CommandOptionType::String)
+                        .required(required)
+                });
+            }
+            option
+        });
+    }
+    command
+}
+
+pub async fn application_command(ctx: &Context, i: &Interaction) {
+    if let Interaction::ApplicationCommand(interaction) = i {
+        let guild = interaction.guild_id.map_or(0, |guild| *guild.as_u64());
+        let (subcommand, options) = match interaction.data.options.first() {
+            Some(subcommand) => (subcommand.name.as_str(), &subcommand.options[..]),
+            None => ("", &[][..]),
+        };
+        let name = string_option(options, "name").unwrap_or_default();
+        let tokens = split_args(&string_option(options, "args").unwrap_or_default());
+        if subcommand == "use" {
+            respond(ctx, interaction, launch(guild, &name, &tokens)).await;
+            return;
+        }
+
+        let (roles, permissions) = match &interaction.member {
+            Some(member) => (member.roles.clone(), member.permissions),
+            None => (Vec::new(), None),
+        };
+        let result = manage(
+            guild,
+            *interaction.user.id.as_u64(),
+            &roles,
+            permissions,
+            subcommand,
+            &name,
+            &tokens,
+        );
+        reply(ctx, interaction, "Template", result).await;
+    }
+}
+
+/// Runs the save, list and delete subcommands.
+fn manage(
+    guild: u64,
+    user: u64,
+    roles: &[RoleId],
+    permissions: Option<Permissions>,
+    subcommand: &str,
+    name: &str,
+    tokens: &[String],
+) -> Result<String> {
+    if guild == 0 {
+        return Err(Error::user("Templates are only available in servers"));
+    }
+    match subcommand {
+        "save" => {
+            let key = key(guild, name)?;
+            if let Some(template) = load(&key)? {
+                if template.creator != user && !is_moderator(roles, permissions) {
+                    return Err(Error::User(format!(
+                        "Template {} belongs to <@{}>",
+                        name, template.creator
+                    )));
+                }
+            }
+            let args = create(tokens, None, config::timezone(guild))?;
+            let template = Template {
+                duration: (args.due - Utc::now()).num_seconds().max(0),
+                args,
+                creator: user,
+            };
+            store::save(Table::Templates, &key, &template)?;
+            Ok(format!("Saved template {}", name))
+        }
+        "list" => {
+            let mut list = Vec::new();
+            for (key, template) in
+                store::scan::<Template>(Table::Templates, &format!("{}/", guild))?
+            {
+                let template = template?;
+                let name = key.split_once('/').map_or("", |(_, name)| name);
+                list.push(format!("`{}`: {}", name, template.args.title));
+            }
+            if list.is_empty() {
+                return Ok("No templates".to_string());
+            }
+            Ok(list.join("\n").chars().take(4096).collect())
+        }
+        "delete" => {
+            let key = key(guild, name)?;
+            let template = load(&key)?.ok_or_else(|| Error::user("Template not found"))?;
+            if template.creator != user && !is_moderator(roles, permissions) {
+                return Err(Error::user(
+                    "Only the owner or moderators can delete this template",
+                ));
+            }
+            STORE.delete(Table::Templates, &key)?;
+            Ok(format!("Deleted template {}", name))
+        }
+        _ => Err(Error::user("Use save, list, use or delete")),
+    }
+}
+
+/// Builds a vote from a template, overridden by the given arguments.
+fn launch(guild: u64, name: &str, tokens: &[String]) -> Result<Args> {
+    if guild == 0 {
+        return Err(Error::user("Templates are only available in servers"));
+    }
+    let mut template =
+        load(&key(guild, name)?)?.ok_or_else(|| Error::user("Template not found"))?;
+    template.args.due = Utc::now() + chrono::Duration::seconds(template.duration);
+    create(tokens, Some(&template.args), config::timezone(guild))
+}
+
+fn key(guild: u64, name: &str) -> Result<String> {
+    if name.is_empty() || name.len() > 32 || name.contains(|c: char| c.is_whitespace() || c == '/')
+    {
+        return Err(Error::user(
+            "Template names must be 1 to 32 characters without spaces",
+        ));
+    }
+    Ok(format!("{}/{}", guild, name.to_lowercase()))
+}
+
+fn load(key: &str) -> Result<Option<Template>> {
+    store::load(Table::Templates, key)
+}
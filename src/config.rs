@@ -1,14 +1,38 @@
+use chrono_tz::Tz;
 use once_cell::sync::Lazy;
 use serde_derive::Deserialize;
-use std::fs;
+use std::{collections::HashMap, fs};
+use tracing::error;
+
+use crate::error::{Error, Result};
 
 #[derive(Deserialize)]
 pub struct Config {
     pub token: String,
     pub id: u64,
+    /// Encrypts stored records
     pub key: String,
+    /// Previous keys, still accepted for reading until rekey is run
+    #[serde(default)]
+    pub old_keys: Vec<String>,
     pub shards: u64,
     pub db: String,
+    #[serde(default)]
+    pub guilds: Vec<u64>,
+    #[serde(default)]
+    pub moderators: Vec<u64>,
+    #[serde(default)]
+    pub retention: Retention,
+    #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
+    pub render: Render,
+    /// Timezone of local due dates such as "tomorrow 18:00"
+    #[serde(default = "utc")]
+    pub timezone: String,
+    /// Timezones overriding the default per guild id
+    #[serde(default)]
+    pub timezones: HashMap<String, String>,
     pub infos: Infos,
 }
 
@@ -19,9 +43,110 @@ pub struct Infos {
     pub activity: String,
 }
 
-pub static CONFIG: Lazy<Config> = Lazy::new(|| init());
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Retention {
+    /// Days without any vote before a poll is purged
+    pub days: i64,
+    /// Hours between purges
+    pub interval: u64,
+    pub keep_ended: bool,
+    pub archive: Archive,
+    pub archive_file: String,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            days: 30,
+            interval: 24,
+            keep_ended: false,
+            archive: Archive::None,
+            archive_file: "archive.jsonl".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Archive {
+    None,
+    /// Keep purged polls in the archive table of the store
+    Tree,
+    /// Append purged polls to archive_file as JSON lines
+    Json,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Storage {
+    pub backend: Backend,
+    /// Database file of the sqlite backend, sled opens db
+    pub sqlite_file: String,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage {
+            backend: Backend::Sled,
+            sqlite_file: "vote.sqlite".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Sled,
+    Sqlite,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Render {
+    /// Milliseconds to collect changes before a poll message is edited
+    pub window: u64,
+    /// Minimum milliseconds between edits of the same poll message
+    pub interval: u64,
+}
+
+impl Default for Render {
+    fn default() -> Self {
+        Render {
+            window: 1000,
+            interval: 3000,
+        }
+    }
+}
+
+pub static CONFIG: Lazy<Config> = Lazy::new(|| match init() {
+    Ok(config) => config,
+    Err(why) => {
+        error!("{}", why);
+        std::process::exit(1);
+    }
+});
+
+pub fn init() -> Result<Config> {
+    let file = fs::read_to_string("./config.toml")
+        .map_err(|why| Error::Config(format!("Conf file read error: {}", why)))?;
+    let config: Config = toml::from_str(&file).map_err(|why| Error::Config(why.to_string()))?;
+    for timezone in config.timezones.values().chain([&config.timezone]) {
+        timezone.parse::<Tz>().map_err(Error::Config)?;
+    }
+    Ok(config)
+}
+
+fn utc() -> String {
+    "UTC".to_string()
+}
 
-pub fn init() -> Config {
-    let file = fs::read_to_string("./config.toml").expect("Conf file read error");
-    toml::from_str(&file).unwrap()
+/// Timezone of the guild, validated by init.
+pub fn timezone(guild: u64) -> Tz {
+    CONFIG
+        .timezones
+        .get(&guild.to_string())
+        .unwrap_or(&CONFIG.timezone)
+        .parse()
+        .unwrap_or(Tz::UTC)
 }
@@ -0,0 +1,216 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use magic_crypt::{new_magic_crypt, MagicCrypt128, MagicCryptTrait};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::CONFIG,
+    error::{Error, Result},
+    store::Table,
+};
+
+/// Tags records sealed with AES-256-GCM, written as
+/// `v1:<key id>:<base64 of nonce and ciphertext>`. Untagged records were
+/// encrypted with magic_crypt and are only read. The table and key of a record
+/// are authenticated along with it, so it doesn't open when moved elsewhere.
+const VERSION: &str = "v1";
+const NONCE: usize = 12;
+
+struct Key {
+    /// Names the key a record was sealed with, without revealing it
+    id: String,
+    cipher: Aes256Gcm,
+    legacy: MagicCrypt128,
+}
+
+impl Key {
+    fn new(secret: &String) -> Self {
+        let key = Sha256::digest(secret.as_bytes());
+        let id = Sha256::digest(key)
+            .iter()
+            .take(4)
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Key {
+            id,
+            cipher: Aes256Gcm::new(&key),
+            legacy: new_magic_crypt!(secret),
+        }
+    }
+}
+
+/// The current key first, then the previous ones still accepted for reading.
+pub struct Keys(Vec<Key>);
+
+pub static KEYS: Lazy<Keys> =
+    Lazy::new(|| Keys::new(std::iter::once(&CONFIG.key).chain(&CONFIG.old_keys)));
+
+impl Keys {
+    pub fn new<'a>(secrets: impl IntoIterator<Item = &'a String>) -> Self {
+        Keys(secrets.into_iter().map(Key::new).collect())
+    }
+
+    /// Seals with the current key and a fresh random nonce.
+    pub fn encrypt(&self, table: Table, key: &str, plain: &str) -> Result<String> {
+        let aad = aad(table, key);
+        let key = &self.0[0];
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = key
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plain.as_bytes(),
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| Error::Crypto("Failed to encrypt".to_string()))?;
+        let mut data = nonce.to_vec();
+        data.extend(sealed);
+        Ok(format!("{}:{}:{}", VERSION, key.id, STANDARD.encode(data)))
+    }
+
+    /// Fails instead of losing data when a record can't be opened, e.g. because
+    /// its key was dropped from the config or it was tampered with.
+    pub fn decrypt(&self, table: Table, key: &str, value: &str) -> Result<String> {
+        let aad = aad(table, key);
+        let plain = match value.split(':').collect::<Vec<&str>>()[..] {
+            [VERSION, id, data] => {
+                let key = self.0.iter().find(|key| key.id == id).ok_or_else(|| {
+                    Error::Corrupted(format!("Record sealed with unknown key {}", id))
+                })?;
+                let data = STANDARD
+                    .decode(data)
+                    .map_err(|why| Error::Corrupted(format!("Record isn't base64: {}", why)))?;
+                if data.len() < NONCE {
+                    return Err(Error::Corrupted("Record is truncated".to_string()));
+                }
+                let (nonce, sealed) = data.split_at(NONCE);
+                key.cipher
+                    .decrypt(
+                        Nonce::from_slice(nonce),
+                        Payload {
+                            msg: sealed,
+                            aad: aad.as_bytes(),
+                        },
+                    )
+                    .map_err(|_| {
+                        Error::Corrupted(format!("Record sealed with key {} failed to open", id))
+                    })?
+            }
+            // A wrong key may still yield valid padding, but hardly valid JSON
+            [legacy] => self
+                .0
+                .iter()
+                .filter_map(|key| key.legacy.decrypt_base64_to_bytes(legacy).ok())
+                .find(|plain| serde_json::from_slice::<serde_json::Value>(plain).is_ok())
+                .ok_or_else(|| Error::Corrupted("Legacy record failed to open".to_string()))?,
+            _ => return Err(Error::Corrupted("Unknown record format".to_string())),
+        };
+        Ok(String::from_utf8(plain)?)
+    }
+
+    /// Whether the record is sealed the way encrypt seals it now.
+    pub fn is_current(&self, value: &str) -> bool {
+        value.starts_with(&format!("{}:{}:", VERSION, self.0[0].id))
+    }
+}
+
+fn aad(table: Table, key: &str) -> String {
+    format!("{}/{}", table.name(), key)
+}
+
+/// Seals with the configured key.
+pub fn encrypt(table: Table, key: &str, plain: &str) -> Result<String> {
+    KEYS.encrypt(table, key, plain)
+}
+
+pub fn decrypt(table: Table, key: &str, value: &str) -> Result<String> {
+    KEYS.decrypt(table, key, value)
+}
+
+pub fn is_current(value: &str) -> bool {
+    KEYS.is_current(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(secrets: &[&str]) -> Keys {
+        let secrets: Vec<String> = secrets.iter().map(|s| s.to_string()).collect();
+        Keys::new(&secrets)
+    }
+
+    fn corrupted(result: Result<String>) -> bool {
+        matches!(result, Err(Error::Corrupted(_)))
+    }
+
+    #[test]
+    fn round_trip() {
+        let keys = keys(&["current"]);
+        let sealed = keys.encrypt(Table::Votes, "1", "{}").unwrap();
+        assert!(sealed.starts_with("v1:"));
+        assert_ne!(sealed, keys.encrypt(Table::Votes, "1", "{}").unwrap());
+        assert_eq!(keys.decrypt(Table::Votes, "1", &sealed).unwrap(), "{}");
+    }
+
+    #[test]
+    fn previous_keys_still_read() {
+        let sealed = keys(&["old"]).encrypt(Table::Polls, "1", "{}").unwrap();
+        let rotated = keys(&["new", "old"]);
+        assert_eq!(rotated.decrypt(Table::Polls, "1", &sealed).unwrap(), "{}");
+        assert!(!rotated.is_current(&sealed));
+        assert!(rotated.is_current(&rotated.encrypt(Table::Polls, "1", "{}").unwrap()));
+    }
+
+    #[test]
+    fn tampered_records_fail() {
+        let keys = keys(&["current"]);
+        let sealed = keys.encrypt(Table::Votes, "1", "{\"votes\":[]}").unwrap();
+        let (head, data) = sealed.rsplit_once(':').unwrap();
+        let mut data = STANDARD.decode(data).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        let tampered = format!("{}:{}", head, STANDARD.encode(data));
+        assert!(corrupted(keys.decrypt(Table::Votes, "1", &tampered)));
+        assert!(corrupted(keys.decrypt(
+            Table::Votes,
+            "1",
+            &format!("{}:AAAA", head)
+        )));
+        assert!(corrupted(keys.decrypt(Table::Votes, "1", "v1:x")));
+    }
+
+    #[test]
+    fn moved_records_fail() {
+        let keys = keys(&["current"]);
+        let sealed = keys.encrypt(Table::Votes, "1", "{}").unwrap();
+        assert!(corrupted(keys.decrypt(Table::Polls, "1", &sealed)));
+        assert!(corrupted(keys.decrypt(Table::Votes, "2", &sealed)));
+    }
+
+    #[test]
+    fn unknown_keys_fail() {
+        let sealed = keys(&["dropped"]).encrypt(Table::Votes, "1", "{}").unwrap();
+        assert!(corrupted(keys(&["current"]).decrypt(
+            Table::Votes,
+            "1",
+            &sealed
+        )));
+    }
+
+    #[test]
+    fn legacy_records_read() {
+        let legacy = new_magic_crypt!("old").encrypt_str_to_base64("{\"a\":1}");
+        let keys = keys(&["new", "old"]);
+        assert_eq!(
+            keys.decrypt(Table::Votes, "1", &legacy).unwrap(),
+            "{\"a\":1}"
+        );
+        assert!(!keys.is_current(&legacy));
+    }
+}
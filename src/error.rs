@@ -0,0 +1,106 @@
+use std::fmt;
+
+use plotters::drawing::DrawingAreaErrorKind;
+use tracing::error;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Caused by the request itself, shown back to the user
+    User(String),
+    Config(String),
+    Db(sled::Error),
+    Sql(rusqlite::Error),
+    /// Stored data which can't be read back
+    Corrupted(String),
+    Json(serde_json::Error),
+    Serenity(Box<serenity::Error>),
+    Io(std::io::Error),
+    Chart(String),
+    Crypto(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn user<T: ToString>(msg: T) -> Self {
+        Error::User(msg.to_string())
+    }
+
+    /// Logs internal errors and returns the text to show to the user.
+    pub fn report(&self) -> String {
+        match self {
+            Error::User(msg) => msg.clone(),
+            why => {
+                error!("{}", why);
+                "Something went wrong, please try again later".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::User(msg) => write!(f, "{}", msg),
+            Error::Config(msg) => write!(f, "Config error: {}", msg),
+            Error::Db(why) => write!(f, "DB error: {}", why),
+            Error::Sql(why) => write!(f, "SQL error: {}", why),
+            Error::Corrupted(msg) => write!(f, "Corrupted data: {}", msg),
+            Error::Json(why) => write!(f, "JSON error: {}", why),
+            Error::Serenity(why) => write!(f, "Discord error: {}", why),
+            Error::Io(why) => write!(f, "IO error: {}", why),
+            Error::Chart(msg) => write!(f, "Chart error: {}", msg),
+            Error::Crypto(msg) => write!(f, "Crypto error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<sled::Error> for Error {
+    fn from(why: sled::Error) -> Self {
+        Error::Db(why)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(why: rusqlite::Error) -> Self {
+        Error::Sql(why)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(why: serde_json::Error) -> Self {
+        Error::Json(why)
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(why: serenity::Error) -> Self {
+        Error::Serenity(Box::new(why))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(why: std::io::Error) -> Self {
+        Error::Io(why)
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(why: std::string::FromUtf8Error) -> Self {
+        Error::Corrupted(why.to_string())
+    }
+}
+
+impl<E: std::error::Error + Send + Sync> From<DrawingAreaErrorKind<E>> for Error {
+    fn from(why: DrawingAreaErrorKind<E>) -> Self {
+        Error::Chart(why.to_string())
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(why: png::EncodingError) -> Self {
+        Error::Chart(why.to_string())
+    }
+}
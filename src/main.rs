@@ -1,26 +1,37 @@
 mod cmds;
 mod config;
+mod crypto;
+mod error;
+mod store;
 pub mod built_info {
     // The file has been placed there by the build script.
     include!(concat!(env!("OUT_DIR"), "/built.rs"));
 }
 
-use cmds::{help::*, ping::*, vote::*};
+use cmds::{
+    help::*,
+    ping::*,
+    vote::{export::*, recurring::*, template::*, *},
+};
 use serenity::{
     async_trait,
     client::{Client, Context, EventHandler},
     framework::{standard::macros::group, StandardFramework},
     model::{
+        application::{
+            command::Command,
+            interaction::{Interaction, InteractionType},
+        },
         gateway::Ready,
-        application::interaction::{Interaction, InteractionType},
+        id::GuildId,
         prelude::Activity,
     },
     prelude::GatewayIntents,
 };
-use tracing::info;
+use tracing::{error, info, warn};
 
 #[group]
-#[commands(ping, vote, help)]
+#[commands(ping, vote, export, template, schedule, help)]
 struct General;
 
 struct Handler;
@@ -28,11 +39,44 @@ struct Handler;
 #[async_trait]
 impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, ready: Ready) {
-        info!("Connected as {}", ready.user.name);
+        info!(
+            "{} connected as {}",
+            config::CONFIG.infos.name,
+            ready.user.name
+        );
         ctx.set_activity(Activity::playing(&config::CONFIG.infos.activity))
             .await;
+        if config::CONFIG.guilds.is_empty() {
+            if let Err(why) = Command::set_global_application_commands(&ctx.http, |commands| {
+                cmds::register(commands)
+            })
+            .await
+            {
+                warn!("Failed to register commands: {}", why);
+            }
+        }
+        for guild in &config::CONFIG.guilds {
+            if let Err(why) = GuildId(*guild)
+                .set_application_commands(&ctx.http, |commands| cmds::register(commands))
+                .await
+            {
+                warn!("Failed to register commands in {}: {}", guild, why);
+            }
+        }
     }
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::ApplicationCommand(command) = &interaction {
+            match command.data.name.as_str() {
+                "vote" => cmds::vote::application_command(&ctx, &interaction).await,
+                "export" => cmds::vote::export::application_command(&ctx, &interaction).await,
+                "poll" => cmds::vote::query::application_command(&ctx, &interaction).await,
+                "template" => cmds::vote::template::application_command(&ctx, &interaction).await,
+                "schedule" => {
+                    cmds::vote::recurring::application_command(&ctx, &interaction).await
+                }
+                _ => {}
+            }
+        }
         if interaction.kind() == InteractionType::MessageComponent {
             if let Interaction::MessageComponent(msg) = interaction.clone() {
                 match &*msg.data.custom_id.to_string() {
@@ -54,6 +98,31 @@ impl EventHandler for Handler {
 
 #[tokio::main]
 async fn main() {
+    tracing_subscriber::fmt::init();
+
+    // Run `vote_bot rekey` with the bot stopped after changing the key
+    if std::env::args().nth(1).as_deref() == Some("rekey") {
+        match store::rekey(store::STORE.as_ref()) {
+            Ok(count) => info!("Re-encrypted {} record(s)", count),
+            Err(why) => {
+                error!("Failed to re-encrypt: {}", why);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    // Run `vote_bot import` with the bot stopped after changing the storage backend
+    if std::env::args().nth(1).as_deref() == Some("import") {
+        match store::import() {
+            Ok(count) => info!("Imported {} record(s)", count),
+            Err(why) => {
+                error!("Failed to import: {}", why);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let framework = StandardFramework::new()
         .configure(|c| {
             c.prefix(&config::CONFIG.infos.prefix);
@@ -74,8 +143,12 @@ async fn main() {
         .await
         .expect("Error creating client");
 
+    cmds::vote::start_scheduler(client.cache_and_http.http.clone());
+    cmds::vote::start_purger();
+    cmds::vote::recurring::start_recurring(client.cache_and_http.http.clone());
+
     // start listening for events by starting a single shard
     if let Err(why) = client.start_shards(config::CONFIG.shards).await {
-        println!("An error occurred while running the client: {:?}", why);
+        error!("An error occurred while running the client: {:?}", why);
     }
 }
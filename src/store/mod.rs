@@ -0,0 +1,202 @@
+use std::path::Path;
+
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::error;
+
+use crate::{
+    config::{Backend, CONFIG},
+    crypto::{self, Keys, KEYS},
+    error::{Error, Result},
+};
+
+mod sled;
+mod sqlite;
+
+pub use self::{sled::SledStore, sqlite::SqliteStore};
+
+/// Everything the bot persists, each kept apart as a tree or table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Table {
+    Votes,
+    Polls,
+    /// Purged polls, when retention archives to the store
+    Archive,
+    Templates,
+    Schedules,
+}
+
+impl Table {
+    pub const ALL: [Table; 5] = [
+        Table::Votes,
+        Table::Polls,
+        Table::Archive,
+        Table::Templates,
+        Table::Schedules,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::Votes => "votes",
+            Table::Polls => "polls",
+            Table::Archive => "archive",
+            Table::Templates => "templates",
+            Table::Schedules => "schedules",
+        }
+    }
+}
+
+/// Keys are poll ids or names, values are the encrypted records.
+pub trait PollStore: Send + Sync {
+    fn get(&self, table: Table, key: &str) -> Result<Option<String>>;
+
+    fn put(&self, table: Table, key: &str, value: &str) -> Result<()>;
+
+    fn delete(&self, table: Table, key: &str) -> Result<()>;
+
+    /// Entries whose key starts with `prefix`, ordered by key.
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, String)>>;
+
+    /// Replaces the value with what `f` makes of the current one, `None` deleting
+    /// it, without any other write in between. `f` may run more than once, and an
+    /// error from it leaves the value untouched. Returns the new value.
+    fn update(
+        &self,
+        table: Table,
+        key: &str,
+        f: &dyn Fn(Option<&str>) -> Result<Option<String>>,
+    ) -> Result<Option<String>>;
+
+    /// Unique ids, e.g. for schedules.
+    fn generate_id(&self) -> Result<u64>;
+
+    /// Makes `generate_id` hand out ids above `id` from now on, e.g. after
+    /// records keyed by ids of another store were copied in.
+    fn reserve_ids(&self, id: u64) -> Result<()>;
+}
+
+pub static STORE: Lazy<Box<dyn PollStore>> = Lazy::new(|| match open() {
+    Ok(store) => store,
+    Err(why) => {
+        error!("Failed to open the store: {}", why);
+        std::process::exit(1);
+    }
+});
+
+/// Serializes a record and seals it for `key` of `table`.
+pub fn encode<T: Serialize>(keys: &Keys, table: Table, key: &str, record: &T) -> Result<String> {
+    keys.encrypt(table, key, &serde_json::to_string(record)?)
+}
+
+/// Opens a record sealed for `key` of `table` and deserializes it.
+pub fn decode<T: DeserializeOwned>(keys: &Keys, table: Table, key: &str, value: &str) -> Result<T> {
+    Ok(serde_json::from_str(&keys.decrypt(table, key, value)?)?)
+}
+
+/// The record at `key` in the configured store.
+pub fn load<T: DeserializeOwned>(table: Table, key: &str) -> Result<Option<T>> {
+    STORE
+        .get(table, key)?
+        .map(|value| decode(&KEYS, table, key, &value))
+        .transpose()
+}
+
+/// Seals the record into the configured store.
+pub fn save<T: Serialize>(table: Table, key: &str, record: &T) -> Result<()> {
+    STORE.put(table, key, &encode(&KEYS, table, key, record)?)
+}
+
+/// Records whose key starts with `prefix`, each decoded on its own so one
+/// unreadable record doesn't hide the others.
+pub fn scan<T: DeserializeOwned>(table: Table, prefix: &str) -> Result<Vec<(String, Result<T>)>> {
+    Ok(STORE
+        .scan(table, prefix)?
+        .into_iter()
+        .map(|(key, value)| {
+            let record = decode(&KEYS, table, &key, &value);
+            (key, record)
+        })
+        .collect())
+}
+
+/// [`PollStore::update`] on decoded records, returning the new record.
+pub fn update<T: Serialize + DeserializeOwned>(
+    store: &dyn PollStore,
+    keys: &Keys,
+    table: Table,
+    key: &str,
+    f: impl Fn(Option<T>) -> Result<Option<T>>,
+) -> Result<Option<T>> {
+    store
+        .update(table, key, &|value| {
+            let record = value
+                .map(|value| decode(keys, table, key, value))
+                .transpose()?;
+            f(record)?
+                .map(|record| encode(keys, table, key, &record))
+                .transpose()
+        })?
+        .map(|value| decode(keys, table, key, &value))
+        .transpose()
+}
+
+/// Re-encrypts every record not sealed with the current key, so previous keys
+/// can be dropped from the config afterwards. Stops at the first record which
+/// fails to open.
+pub fn rekey(store: &dyn PollStore) -> Result<usize> {
+    let mut count = 0;
+    for table in Table::ALL {
+        for (key, value) in store.scan(table, "")? {
+            if crypto::is_current(&value) {
+                continue;
+            }
+            store
+                .update(table, &key, &|value| {
+                    value
+                        .map(|value| {
+                            crypto::encrypt(table, &key, &crypto::decrypt(table, &key, value)?)
+                        })
+                        .transpose()
+                })
+                .inspect_err(|_| error!("Failed to re-encrypt {} {}", table.name(), key))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Copies every record of the backend which isn't configured into the configured
+/// one as it is, still encrypted, so switching backends keeps existing polls.
+pub fn import() -> Result<usize> {
+    let from: Box<dyn PollStore> = match CONFIG.storage.backend {
+        Backend::Sled if Path::new(&CONFIG.storage.sqlite_file).exists() => {
+            Box::new(SqliteStore::open(&CONFIG.storage.sqlite_file)?)
+        }
+        Backend::Sqlite if Path::new(&CONFIG.db).exists() => Box::new(SledStore::open(&CONFIG.db)?),
+        _ => return Err(Error::Config("Nothing to import".to_string())),
+    };
+    copy(from.as_ref(), STORE.as_ref())
+}
+
+fn copy(from: &dyn PollStore, to: &dyn PollStore) -> Result<usize> {
+    let mut count = 0;
+    let mut last_id = 0;
+    for table in Table::ALL {
+        for (key, value) in from.scan(table, "")? {
+            if table == Table::Schedules {
+                last_id = key.parse().unwrap_or(0).max(last_id);
+            }
+            to.put(table, &key, &value)?;
+            count += 1;
+        }
+    }
+    to.reserve_ids(last_id)?;
+    Ok(count)
+}
+
+fn open() -> Result<Box<dyn PollStore>> {
+    Ok(match CONFIG.storage.backend {
+        Backend::Sled => Box::new(SledStore::open(&CONFIG.db)?),
+        Backend::Sqlite => Box::new(SqliteStore::open(&CONFIG.storage.sqlite_file)?),
+    })
+}
@@ -0,0 +1,93 @@
+use ::sled::{
+    transaction::{ConflictableTransactionError, TransactionError},
+    Db, IVec, Tree,
+};
+
+use super::{PollStore, Table};
+use crate::error::{Error, Result};
+
+pub struct SledStore {
+    db: Db,
+}
+
+impl SledStore {
+    pub fn open(path: &str) -> Result<Self> {
+        Ok(SledStore {
+            db: ::sled::open(path)?,
+        })
+    }
+
+    /// Votes stay in the default tree, where they were kept before any other tree.
+    fn tree(&self, table: Table) -> Result<Tree> {
+        match table {
+            Table::Votes => Ok((*self.db).clone()),
+            table => Ok(self.db.open_tree(table.name())?),
+        }
+    }
+}
+
+fn string(value: IVec) -> Result<String> {
+    Ok(String::from_utf8(value.to_vec())?)
+}
+
+impl PollStore for SledStore {
+    fn get(&self, table: Table, key: &str) -> Result<Option<String>> {
+        self.tree(table)?.get(key)?.map(string).transpose()
+    }
+
+    fn put(&self, table: Table, key: &str, value: &str) -> Result<()> {
+        self.tree(table)?.insert(key, value.as_bytes())?;
+        Ok(())
+    }
+
+    fn delete(&self, table: Table, key: &str) -> Result<()> {
+        self.tree(table)?.remove(key)?;
+        Ok(())
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, String)>> {
+        self.tree(table)?
+            .scan_prefix(prefix)
+            .map(|entry| {
+                let (key, value) = entry?;
+                Ok((string(key)?, string(value)?))
+            })
+            .collect()
+    }
+
+    fn update(
+        &self,
+        table: Table,
+        key: &str,
+        f: &dyn Fn(Option<&str>) -> Result<Option<String>>,
+    ) -> Result<Option<String>> {
+        self.tree(table)?
+            .transaction(|tx| {
+                let current = tx
+                    .get(key)?
+                    .map(string)
+                    .transpose()
+                    .map_err(ConflictableTransactionError::Abort)?;
+                let value = f(current.as_deref()).map_err(ConflictableTransactionError::Abort)?;
+                match &value {
+                    Some(value) => tx.insert(key, value.as_bytes())?,
+                    None => tx.remove(key)?,
+                };
+                Ok(value)
+            })
+            .map_err(|why| match why {
+                TransactionError::Abort(why) => why,
+                TransactionError::Storage(why) => Error::Db(why),
+            })
+    }
+
+    fn generate_id(&self) -> Result<u64> {
+        Ok(self.db.generate_id()?)
+    }
+
+    /// Sled can't move its counter, so ids are drawn until past `id`.
+    fn reserve_ids(&self, id: u64) -> Result<()> {
+        while self.db.generate_id()? < id {}
+        Ok(())
+    }
+}
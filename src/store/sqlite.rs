@@ -0,0 +1,128 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+
+use super::{PollStore, Table};
+use crate::error::Result;
+
+/// A single-file database with a `key`/`value` table per [`Table`]. Values are
+/// the encrypted records, so beyond listing keys the data can't be queried
+/// with SQL.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        for table in Table::ALL {
+            conn.execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+                    table.name()
+                ),
+                [],
+            )?;
+        }
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ids (id INTEGER PRIMARY KEY AUTOINCREMENT)",
+            [],
+        )?;
+        Ok(SqliteStore {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn get(conn: &Connection, table: Table, key: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT value FROM {} WHERE key = ?1", table.name()),
+            [key],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn put(conn: &Connection, table: Table, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO {} (key, value) VALUES (?1, ?2)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            table.name()
+        ),
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+fn delete(conn: &Connection, table: Table, key: &str) -> Result<()> {
+    conn.execute(
+        &format!("DELETE FROM {} WHERE key = ?1", table.name()),
+        [key],
+    )?;
+    Ok(())
+}
+
+impl PollStore for SqliteStore {
+    fn get(&self, table: Table, key: &str) -> Result<Option<String>> {
+        get(&self.conn(), table, key)
+    }
+
+    fn put(&self, table: Table, key: &str, value: &str) -> Result<()> {
+        put(&self.conn(), table, key, value)
+    }
+
+    fn delete(&self, table: Table, key: &str) -> Result<()> {
+        delete(&self.conn(), table, key)
+    }
+
+    fn scan(&self, table: Table, prefix: &str) -> Result<Vec<(String, String)>> {
+        let conn = self.conn();
+        // Keys compare bytewise like sled, and substr avoids escaping LIKE patterns
+        let mut statement = conn.prepare(&format!(
+            "SELECT key, value FROM {} WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key",
+            table.name()
+        ))?;
+        let rows = statement.query_map([prefix], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn update(
+        &self,
+        table: Table,
+        key: &str,
+        f: &dyn Fn(Option<&str>) -> Result<Option<String>>,
+    ) -> Result<Option<String>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let value = f(get(&tx, table, key)?.as_deref())?;
+        match &value {
+            Some(value) => put(&tx, table, key, value)?,
+            None => delete(&tx, table, key)?,
+        }
+        tx.commit()?;
+        Ok(value)
+    }
+
+    fn generate_id(&self) -> Result<u64> {
+        let conn = self.conn();
+        // AUTOINCREMENT never hands out an id twice, even once its row is gone
+        conn.execute("INSERT INTO ids DEFAULT VALUES", [])?;
+        let id = conn.last_insert_rowid();
+        conn.execute("DELETE FROM ids WHERE id = ?1", [id])?;
+        Ok(id as u64)
+    }
+
+    fn reserve_ids(&self, id: u64) -> Result<()> {
+        let conn = self.conn();
+        // Inserting an id raises the AUTOINCREMENT sequence to at least that id
+        conn.execute("INSERT OR IGNORE INTO ids (id) VALUES (?1)", [id as i64])?;
+        conn.execute("DELETE FROM ids WHERE id = ?1", [id as i64])?;
+        Ok(())
+    }
+}